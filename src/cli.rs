@@ -0,0 +1,100 @@
+use clap::Parser;
+
+use crate::session::Session;
+
+/// Genome graph and annotation browser
+#[derive(Parser, Debug)]
+#[clap(version)]
+pub struct Args {
+    /// GFA graph, can be given more than once
+    #[clap(long, value_name = "FILE")]
+    pub gfa: Vec<String>,
+
+    /// GFF3 annotation, plain or BGZF with a tabix index
+    #[clap(long, value_name = "FILE")]
+    pub gff3: Vec<String>,
+
+    #[clap(long, value_name = "FILE")]
+    pub gtf: Vec<String>,
+
+    /// Reference sequence, needs a .fai alongside it
+    #[clap(long, value_name = "FILE")]
+    pub fasta: Vec<String>,
+
+    #[clap(long, value_name = "FILE")]
+    pub bed: Vec<String>,
+
+    #[clap(long, value_name = "FILE")]
+    pub vcf: Vec<String>,
+
+    /// Where to start: chr1:10,000-20,000, chr1:5,000, a landmark or a feature name
+    #[clap(long)]
+    pub locus: Option<String>,
+
+    /// Session file with more inputs and a locus, see Session
+    #[clap(long, value_name = "FILE")]
+    pub session: Option<String>,
+}
+
+impl Args {
+    /// Session file first, then everything given here. A --locus wins over the session's.
+    pub fn session(&self) -> Result<Session, String> {
+        let mut session = match &self.session {
+            Some(x) => Session::load(x)?,
+            None => Session::default(),
+        };
+
+        let inputs = [
+            ("gfa", &self.gfa),
+            ("gff3", &self.gff3),
+            ("gtf", &self.gtf),
+            ("fasta", &self.fasta),
+            ("bed", &self.bed),
+            ("vcf", &self.vcf),
+        ];
+        for (kind, filenames) in inputs {
+            for filename in filenames {
+                session.add(kind, filename)?;
+            }
+        }
+
+        if self.locus.is_some() {
+            session.locus = self.locus.clone();
+        }
+
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loading::FileKind;
+
+    #[test]
+    fn test_session() {
+        let args = Args::parse_from([
+            "sbrowser",
+            "--session",
+            "test_data/test.session",
+            "--bed",
+            "test_data/test.bed",
+        ]);
+        let session = args.session().unwrap();
+        assert_eq!(session.inputs.len(), 4);
+        assert_eq!(
+            session.inputs[3],
+            (FileKind::Bed, "test_data/test.bed".to_string())
+        );
+        assert_eq!(session.locus.as_deref(), Some("chr1:10,000-20,000"));
+
+        let args = Args::parse_from([
+            "sbrowser",
+            "--session",
+            "test_data/test.session",
+            "--locus",
+            "chr2:500",
+        ]);
+        assert_eq!(args.session().unwrap().locus.as_deref(), Some("chr2:500"));
+    }
+}
@@ -0,0 +1,327 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use crossbeam::channel::{bounded, Receiver};
+
+use std::path::PathBuf;
+
+use crate::core::states::*;
+use crate::parsers::*;
+use crate::structs::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    Gfa,
+    Gff3,
+    Gtf,
+    Fasta,
+    Bed,
+    Vcf,
+}
+
+impl FileKind {
+    /// As written on the command line and in session files
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "gfa" => Some(FileKind::Gfa),
+            "gff3" | "gff" => Some(FileKind::Gff3),
+            "gtf" => Some(FileKind::Gtf),
+            "fasta" | "fa" => Some(FileKind::Fasta),
+            "bed" | "bedgraph" => Some(FileKind::Bed),
+            "vcf" => Some(FileKind::Vcf),
+            _ => None,
+        }
+    }
+
+    /// What's in the file, going by its contents first
+    pub fn sniff(filename: &str) -> Result<Self, String> {
+        match Format::sniff(filename).map_err(|x| x.to_string())? {
+            Format::Gfa => Ok(FileKind::Gfa),
+            Format::Gff3 => Ok(FileKind::Gff3),
+            Format::Gtf => Ok(FileKind::Gtf),
+            Format::Fasta => Ok(FileKind::Fasta),
+            Format::Bed => Ok(FileKind::Bed),
+            Format::Vcf => Ok(FileKind::Vcf),
+        }
+    }
+
+    pub fn options(&self) -> ParseOptions {
+        match self {
+            // Assemblers' GFA is often a little off, better to see most of it than none
+            FileKind::Gfa => ParseOptions::lenient(),
+            _ => ParseOptions::default(),
+        }
+    }
+}
+
+enum Loaded {
+    Gfa(Gfa),
+    Gff3(Gff3),
+    Gtf(Gtf),
+    Fasta(Fasta),
+    Bed(Bed),
+    Vcf(Vcf),
+}
+
+struct LoadTask {
+    filename: String,
+    progress: Progress,
+    receiver: Receiver<Result<Loaded, ParseError>>,
+}
+
+/// Files being parsed on their own threads, they land in BrowserState when done
+#[derive(Default)]
+pub struct Loading {
+    tasks: Vec<LoadTask>,
+    pub messages: Vec<String>, // Failed loads and skipped records, shown under the progress bars
+}
+
+impl Loading {
+    pub fn load(&mut self, kind: FileKind, filename: &str, options: ParseOptions) {
+        let progress = Progress::new();
+        let options = ParseOptions {
+            progress: Some(progress.clone()),
+            ..options
+        };
+
+        let (sender, receiver) = bounded(1);
+        let name = filename.to_string();
+        std::thread::spawn(move || {
+            let loaded = match kind {
+                FileKind::Gfa => Gfa::parse_with(&name, &options).map(Loaded::Gfa),
+                FileKind::Gff3 => Gff3::parse_with(&name, &options).map(Loaded::Gff3),
+                FileKind::Gtf => Gtf::parse_with(&name, &options).map(Loaded::Gtf),
+                FileKind::Fasta => Fasta::open(&name).map(Loaded::Fasta),
+                FileKind::Bed => Bed::parse_with(&name, &options).map(Loaded::Bed),
+                FileKind::Vcf => Vcf::parse_with(&name, &options).map(Loaded::Vcf),
+            };
+            // Only fails if the app is gone
+            let _ = sender.send(loaded);
+        });
+
+        self.tasks.push(LoadTask {
+            filename: filename.to_string(),
+            progress,
+            receiver,
+        });
+    }
+
+    /// Opened from the menu or dropped on the window, the format is sniffed
+    pub fn open(&mut self, filename: &str) -> Result<(), String> {
+        let kind = FileKind::sniff(filename)?;
+        self.load(kind, filename, kind.options());
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+}
+
+// Files in File > Recent
+const MAX_RECENT: usize = 10;
+
+/// Most recent first, kept in ~/.sbrowser_recent between runs
+pub struct RecentFiles {
+    pub files: Vec<String>,
+}
+
+impl Default for RecentFiles {
+    fn default() -> Self {
+        let files = RecentFiles::path()
+            .and_then(|x| std::fs::read_to_string(x).ok())
+            .map(|x| x.lines().map(|x| x.to_string()).collect())
+            .unwrap_or_default();
+        RecentFiles { files }
+    }
+}
+
+impl RecentFiles {
+    fn path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|x| PathBuf::from(x).join(".sbrowser_recent"))
+    }
+
+    pub fn add(&mut self, filename: &str) {
+        // Stored absolute so they still open from another directory
+        let filename = std::fs::canonicalize(filename)
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_else(|_| filename.to_string());
+
+        self.files.retain(|x| *x != filename);
+        self.files.insert(0, filename);
+        self.files.truncate(MAX_RECENT);
+
+        if let Some(path) = RecentFiles::path() {
+            if let Err(err) = std::fs::write(path, self.files.join("\n")) {
+                println!("Unable to save recent files: {}", err);
+            }
+        }
+    }
+}
+
+// Sent every frame for each file still loading
+pub struct LoadProgress {
+    pub filename: String,
+    pub read: u64,
+    pub total: u64,
+}
+
+pub struct FileLoaded {
+    pub filename: String,
+}
+
+pub struct LoadingPlugin;
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Loading>()
+            .init_resource::<RecentFiles>()
+            .add_event::<LoadProgress>()
+            .add_event::<FileLoaded>()
+            .add_system(open_dropped.before("poll_loading"))
+            .add_system(poll_loading.label("poll_loading"))
+            .add_system(progress_bar.after("poll_loading"));
+    }
+}
+
+fn open_dropped(mut ev_dropped: EventReader<FileDragAndDrop>, mut loading: ResMut<Loading>) {
+    for event in ev_dropped.iter() {
+        if let FileDragAndDrop::DroppedFile { path_buf, .. } = event {
+            if let Err(err) = loading.open(&path_buf.to_string_lossy()) {
+                loading.messages.push(err);
+            }
+        }
+    }
+}
+
+fn poll_loading(
+    mut loading: ResMut<Loading>,
+    mut recent: ResMut<RecentFiles>,
+    mut bstate: ResMut<BrowserState>,
+    mut state: ResMut<State<AppState>>,
+    mut ev_progress: EventWriter<LoadProgress>,
+    mut ev_loaded: EventWriter<FileLoaded>,
+) {
+    if loading.is_empty() {
+        return;
+    }
+
+    let mut done = Vec::new();
+    let mut messages = Vec::new();
+
+    for (n, task) in loading.tasks.iter().enumerate() {
+        let loaded = match task.receiver.try_recv() {
+            Ok(x) => x,
+            Err(_) => {
+                ev_progress.send(LoadProgress {
+                    filename: task.filename.clone(),
+                    read: task.progress.read(),
+                    total: task.progress.total(),
+                });
+                continue;
+            }
+        };
+
+        done.push(n);
+
+        let warnings = match loaded {
+            Ok(Loaded::Gfa(x)) => {
+                let warnings = x.warnings.clone();
+                bstate.gfa = Some(x);
+                warnings
+            }
+            Ok(Loaded::Gff3(x)) => {
+                let warnings = x.warnings.clone();
+                bstate.add_track(TrackSource::Gff3(x));
+                warnings
+            }
+            Ok(Loaded::Gtf(x)) => {
+                let warnings = x.warnings.clone();
+                bstate.add_track(TrackSource::Gtf(x));
+                warnings
+            }
+            Ok(Loaded::Bed(x)) => {
+                let warnings = x.warnings.clone();
+                bstate.add_track(TrackSource::Bed(x));
+                warnings
+            }
+            Ok(Loaded::Vcf(x)) => {
+                let warnings = x.warnings.clone();
+                bstate.add_track(TrackSource::Vcf(x));
+                warnings
+            }
+            Ok(Loaded::Fasta(x)) => {
+                bstate.fasta = Some(x);
+                Warnings::default()
+            }
+            Err(err) => {
+                messages.push(format!("Unable to load {}: {}", task.filename, err));
+                continue;
+            }
+        };
+
+        if let Some(first) = warnings.errors.first() {
+            messages.push(format!(
+                "Skipped {} records of {}, the first at {}",
+                warnings.skipped, task.filename, first
+            ));
+        }
+
+        recent.add(&task.filename);
+        ev_loaded.send(FileLoaded {
+            filename: task.filename.clone(),
+        });
+    }
+
+    if done.is_empty() {
+        return;
+    }
+
+    loading.messages.extend(messages);
+    for n in done.into_iter().rev() {
+        loading.tasks.remove(n);
+    }
+
+    // The overview lists what's loaded when it's entered, so enter it again
+    let changed = match state.current() {
+        _ if !loading.is_empty() => Ok(()),
+        AppState::SequenceOverview => state.restart(),
+        AppState::MainMenu => state.replace(AppState::SequenceOverview),
+        _ => Ok(()),
+    };
+    if let Err(err) = changed {
+        println!("Unable to refresh the overview: {:?}", err);
+    }
+}
+
+fn progress_bar(
+    mut egui_ctx: ResMut<EguiContext>,
+    mut loading: ResMut<Loading>,
+    mut ev_progress: EventReader<LoadProgress>,
+) {
+    let progress = ev_progress.iter().collect::<Vec<&LoadProgress>>();
+    if progress.is_empty() && loading.messages.is_empty() {
+        return;
+    }
+
+    egui::TopBottomPanel::bottom("loading").show(egui_ctx.ctx_mut(), |ui| {
+        for x in progress {
+            let fraction = match x.total {
+                0 => 0.0,
+                total => x.read as f32 / total as f32,
+            };
+            ui.add(egui::ProgressBar::new(fraction).text(format!(
+                "{}: {:.1} / {:.1} MB",
+                x.filename,
+                x.read as f64 / 1e6,
+                x.total as f64 / 1e6
+            )));
+        }
+
+        // Stay until dismissed, there's no log to find them in later
+        for message in loading.messages.iter() {
+            ui.colored_label(egui::Color32::YELLOW, message);
+        }
+        if !loading.messages.is_empty() && ui.button("Dismiss").clicked() {
+            loading.messages.clear();
+        }
+    });
+}
@@ -11,24 +11,31 @@ use bevy::{input::mouse::MouseWheel, pbr::AmbientLight, pbr::PointLightBundle, p
 use bevy::render::camera::*;
 use bevy_egui::{egui, EguiContext, EguiPlugin};
 use bevy_mod_picking::*;
+use clap::Parser;
 
+mod cli;
 mod core;
 mod genome;
 mod hover;
+mod loading;
 mod parsers;
+mod session;
 mod structs;
 mod utils;
 mod views;
 
 use structs::*;
 
+use crate::cli::Args;
 use crate::core::states::*;
 use crate::genome::*;
 use crate::hover::*;
+use crate::loading::*;
 use crate::parsers::feature;
 use crate::parsers::*;
 use crate::structs::*;
 use crate::utils::label_placer::*;
+use crate::views::menu_bar::LocusInput;
 use crate::views::*;
 
 const DRAG_SPEED_COFACTOR: f32 = 0.5;
@@ -36,13 +43,32 @@ const DRAG_SPEED_COFACTOR: f32 = 0.5;
 fn main() {
     // let genome = genome::get_genome_from_gff3("converted.sorted.s.gff3");
 
-    //let genome = Gff3::parse("converted.sorted.s.gff3").expect("Unable to parse GFF3");
-    let genome = Gfa::parse("out.gfa")
-        .expect("Unable to parse GFA");
+    let session = match Args::parse().session() {
+        Ok(x) => x,
+        Err(err) => {
+            eprintln!("Unable to read session {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    // Parsed in the background, see LoadingPlugin. They land in BrowserState as they finish.
+    let mut loading = Loading::default();
+    for (kind, filename) in session.inputs.iter() {
+        loading.load(*kind, filename, kind.options());
+    }
+
+    let bstate = BrowserState::default();
 
-    let mut bstate = BrowserState::default();
-    //bstate.gff3 = Some(genome.clone());
-    bstate.gfa = Some(genome.clone());
+    // Nothing to show without files, the locus is gone to once they are loaded
+    let start = if loading.is_empty() {
+        AppState::MainMenu
+    } else {
+        AppState::SequenceOverview
+    };
+    let locus = match session.locus {
+        Some(x) => LocusInput::pending(&x),
+        None => LocusInput::default(),
+    };
 
     let mut app = App::new();
 
@@ -52,13 +78,16 @@ fn main() {
             color: Color::WHITE,
             brightness: 0.5,
         })
-        .insert_resource(genome)
+        .insert_resource(loading)
         .insert_resource(ClearColor(Color::BLACK))
         // .insert_resource(genome)
         .insert_resource(UISetting::default())
         .insert_resource(bstate)
+        .insert_resource(locus)
         .add_event::<CameraMoved>()
         .add_event::<LoadLandmark>()
+        .add_event::<FrameRange>()
+        .add_event::<TracksChanged>()
         .add_plugins(DefaultPlugins)
         .insert_resource(EntityRegistry::default())
         .insert_resource(ExpansionRounds { round: 0 }) // DEBUG: Probably a temporary thing...
@@ -69,11 +98,14 @@ fn main() {
         .add_plugin(DebugCursorPickingPlugin)
         .add_plugin(DebugEventsPickingPlugin)
         .add_plugin(LabelPlacerPlugin)
+        .add_plugin(LoadingPlugin)
         //.add_plugin(HoverPlugin)
         .add_plugin(MenuBarPlugin)
+        .add_plugin(TrackPanelPlugin)
         .add_plugin(MainMenuPlugin)
         .add_plugin(SequenceOverviewPlugin)
         .add_plugin(SequenceViewPlugin)
+        .add_plugin(VariantInspectorPlugin)
         // .add_plugin(InspectorPlugin::<Hoverable>::new())
         .add_startup_system(setup)
         // .add_startup_system(draw_chromosome.system())
@@ -81,7 +113,7 @@ fn main() {
         .add_system(camera_move)
         .add_system(mouse_scroll)
         //.add_system(hover_highlight.system())
-        .add_state(AppState::SequenceOverview);
+        .add_state(start);
     // .add_system(zoom_chromosome.system())
 
     // registering custom component to be able to edit it in inspector
@@ -173,6 +205,7 @@ fn camera_move(
 
     if !velocity.is_nan() && velocity != Vec3::ZERO {
         transform.translation += velocity * time.delta_seconds();
+        ui_setting.zoom_factor = 1.0 + transform.scale.x;
         ev_cameramoved.send(CameraMoved);
     }
 }
@@ -187,10 +220,9 @@ fn mouse_scroll(
     let (_camera, mut transform) = query.single_mut();
 
     for event in mouse_wheel_events.iter() {
-        ui_setting.zoom_factor -= event.y;
-        println!("Zoom Factor: {}", ui_setting.zoom_factor);
         // transform.scale += -event.y * Vec3::new(0.01, 0.00, 0.0);
         transform.scale.x += -event.y * 0.01 * transform.scale.x;
+        ui_setting.zoom_factor = 1.0 + transform.scale.x;
 
         ev_cameramoved.send(CameraMoved);
 
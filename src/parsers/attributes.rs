@@ -0,0 +1,186 @@
+use twox_hash::RandomXxh3HashBuilder64;
+
+use std::collections::HashMap;
+
+use super::error::*;
+use crate::structs::Orientation;
+
+// Target=EST23 1 21 +
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Target {
+    pub id: String,
+    pub start: usize,
+    pub end: usize,
+    pub strand: Option<Orientation>,
+}
+
+// GFF3 column 9. Reserved keys get their own fields, everything else lands in other
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Attributes {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub alias: Vec<String>,
+    pub parent: Vec<String>,
+    pub target: Option<Target>,
+    pub dbxref: Vec<String>,
+    pub ontology_term: Vec<String>,
+    pub other: HashMap<String, Vec<String>, RandomXxh3HashBuilder64>,
+}
+
+impl Attributes {
+    pub fn from_gff3(column: &str) -> Result<Attributes, ParseError> {
+        let mut attributes = Attributes::default();
+
+        if column == "." {
+            return Ok(attributes);
+        }
+
+        for pair in column
+            .split(';')
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+        {
+            let (key, value) = match pair.split_once('=') {
+                Some(x) => x,
+                None => {
+                    return Err(
+                        ParseError::new(ParseErrorKind::InvalidValue).with_field(9, "attributes")
+                    )
+                }
+            };
+
+            let values = || {
+                value
+                    .split(',')
+                    .map(percent_decode)
+                    .collect::<Vec<String>>()
+            };
+
+            match key {
+                "ID" => attributes.id = Some(percent_decode(value)),
+                "Name" => attributes.name = Some(percent_decode(value)),
+                "Alias" => attributes.alias.extend(values()),
+                "Parent" => attributes.parent.extend(values()),
+                "Dbxref" => attributes.dbxref.extend(values()),
+                "Ontology_term" => attributes.ontology_term.extend(values()),
+                "Target" => attributes.target = Some(parse_target(value)?),
+                _ => attributes
+                    .other
+                    .entry(percent_decode(key))
+                    .or_default()
+                    .extend(values()),
+            }
+        }
+
+        Ok(attributes)
+    }
+
+    /// Values for any key, reserved or not, as they appeared in the file
+    pub fn get(&self, key: &str) -> Option<Vec<String>> {
+        match key {
+            "ID" => self.id.clone().map(|x| vec![x]),
+            "Name" => self.name.clone().map(|x| vec![x]),
+            "Alias" if !self.alias.is_empty() => Some(self.alias.clone()),
+            "Parent" if !self.parent.is_empty() => Some(self.parent.clone()),
+            "Dbxref" if !self.dbxref.is_empty() => Some(self.dbxref.clone()),
+            "Ontology_term" if !self.ontology_term.is_empty() => Some(self.ontology_term.clone()),
+            "Target" => self.target.as_ref().map(|x| {
+                let strand = match x.strand {
+                    Some(Orientation::Positive) => " +",
+                    Some(Orientation::Negative) => " -",
+                    None => "",
+                };
+                vec![format!("{} {} {}{}", x.id, x.start, x.end, strand)]
+            }),
+            _ => self.other.get(key).cloned(),
+        }
+    }
+}
+
+fn parse_target(value: &str) -> Result<Target, ParseError> {
+    let invalid = || ParseError::new(ParseErrorKind::InvalidValue).with_field(9, "Target");
+
+    let split = value.split(' ').collect::<Vec<&str>>();
+    if split.len() < 3 || split.len() > 4 {
+        return Err(invalid());
+    }
+
+    Ok(Target {
+        id: percent_decode(split[0]),
+        start: split[1].parse::<usize>().map_err(|_| invalid())?,
+        end: split[2].parse::<usize>().map_err(|_| invalid())?,
+        strand: match split.get(3) {
+            Some(x) => Some(x.parse::<Orientation>().map_err(|_| invalid())?),
+            None => None,
+        },
+    })
+}
+
+/// Decodes %XX escapes, anything that isn't a valid escape is kept as-is
+pub fn percent_decode(value: &str) -> String {
+    if !value.contains('%') {
+        return value.to_string();
+    }
+
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        // Two hex digits exactly, from_str_radix alone would take %+1
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap();
+            decoded.push(u8::from_str_radix(hex, 16).unwrap());
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_attributes() {
+        let attributes = Attributes::from_gff3(
+            "ID=mRNA00001;Parent=gene1,gene2;Name=EDEN%3B1;Dbxref=EMBL:AA816246,NCBI_gi:10727410;Note=a%2Cb;",
+        )
+        .unwrap();
+
+        assert_eq!(attributes.id.as_deref(), Some("mRNA00001"));
+        assert_eq!(attributes.name.as_deref(), Some("EDEN;1"));
+        assert_eq!(attributes.parent, vec!["gene1", "gene2"]);
+        assert_eq!(attributes.dbxref.len(), 2);
+        assert_eq!(attributes.get("Note"), Some(vec!["a,b".to_string()]));
+        assert_eq!(attributes.get("Parent").unwrap().len(), 2);
+        assert!(attributes.get("Alias").is_none());
+    }
+
+    #[test]
+    fn test_parse_target() {
+        let attributes = Attributes::from_gff3("Target=EST%2023 1 21 +").unwrap();
+        let target = attributes.target.unwrap();
+        assert_eq!(target.id, "EST 23");
+        assert_eq!(target.end, 21);
+        assert_eq!(target.strand, Some(Orientation::Positive));
+
+        assert!(Attributes::from_gff3("Target=EST23 1").is_err());
+        assert!(Attributes::from_gff3("ID").is_err());
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%20b%"), "a b%");
+        assert_eq!(percent_decode("%zz%41"), "%zzA");
+        assert_eq!(percent_decode("%+1%-1"), "%+1%-1");
+    }
+}
@@ -0,0 +1,501 @@
+use simdutf8::basic::from_utf8;
+use twox_hash::RandomXxh3HashBuilder64;
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use super::bgzf::*;
+use super::error::*;
+use super::feature::*;
+use super::gff3::Landmark;
+use super::progress::*;
+use crate::structs::Orientation;
+
+const BED_COLUMNS: [&str; 12] = [
+    "chrom",
+    "chromStart",
+    "chromEnd",
+    "name",
+    "score",
+    "strand",
+    "thickStart",
+    "thickEnd",
+    "itemRgb",
+    "blockCount",
+    "blockSizes",
+    "blockStarts",
+];
+
+const BEDGRAPH_COLUMNS: [&str; 4] = ["chrom", "chromStart", "chromEnd", "dataValue"];
+
+// Settings from the track line, the ones that change how the file is drawn
+#[derive(Clone, Debug, Default)]
+pub struct BedTrackLine {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub graph: bool,    // type=bedGraph
+    pub item_rgb: bool, // itemRgb=On, otherwise the colors in the records are ignored
+    pub color: Option<[u8; 3]>,
+}
+
+impl BedTrackLine {
+    // track name="Peaks" description="MACS2 peaks" itemRgb=On, values may be quoted
+    fn parse(line: &str) -> Self {
+        let mut track = BedTrackLine::default();
+        let mut rest = line.strip_prefix("track").unwrap_or(line).trim_start();
+
+        while let Some((key, after)) = rest.split_once('=') {
+            let (value, after) = match after.strip_prefix('"') {
+                Some(x) => x.split_once('"').unwrap_or((x, "")),
+                None => after.split_once(char::is_whitespace).unwrap_or((after, "")),
+            };
+
+            match key.trim() {
+                "name" => track.name = Some(value.to_string()),
+                "description" => track.description = Some(value.to_string()),
+                "type" => track.graph = value.eq_ignore_ascii_case("bedGraph"),
+                "itemRgb" => track.item_rgb = value.eq_ignore_ascii_case("on"),
+                "color" => track.color = parse_rgb(value),
+                _ => (),
+            }
+
+            rest = after.trim_start();
+        }
+
+        track
+    }
+}
+
+/// BED3 through BED12 as features, or bedGraph as (start, end, value) signal. Coordinates in
+/// signal are 0-based and half-open as in the file, features are converted to 1-based like GFF.
+/// BED files are seldom large and often unsorted, so all of it is read up front.
+#[derive(Clone, Debug)]
+pub struct Bed {
+    pub filename: String,
+    pub track: BedTrackLine,
+    pub landmarks: Vec<Landmark>,
+    pub features: HashMap<String, Vec<Feature>, RandomXxh3HashBuilder64>,
+    pub signal: HashMap<String, Vec<(usize, usize, f64)>, RandomXxh3HashBuilder64>,
+    pub warnings: Warnings,
+}
+
+impl Bed {
+    pub fn parse<T>(filename: T) -> Result<Bed, ParseError>
+    where
+        T: ToString,
+    {
+        Bed::parse_with(filename, &ParseOptions::default())
+    }
+
+    pub fn parse_with<T>(filename: T, options: &ParseOptions) -> Result<Bed, ParseError>
+    where
+        T: ToString,
+    {
+        let filename = filename.to_string();
+        let mut file = match InputFile::open(&filename) {
+            Ok(x) => x,
+            Err(err) => return Err(ParseError::from(err).with_file(&filename)),
+        };
+
+        let lower = filename.to_ascii_lowercase();
+        let mut bed = Bed {
+            track: BedTrackLine {
+                graph: lower.contains(".bedgraph") || lower.contains(".bdg"),
+                ..Default::default()
+            },
+            filename,
+            landmarks: Vec::new(),
+            features: HashMap::default(),
+            signal: HashMap::default(),
+            warnings: Warnings::default(),
+        };
+
+        if let Some(progress) = &options.progress {
+            progress.start(&bed.filename);
+        }
+
+        let mut line: Vec<u8> = Vec::with_capacity(8192);
+        let mut line_number: usize = 0;
+
+        loop {
+            line.clear();
+            line_number += 1;
+
+            if line_number % PROGRESS_LINES == 0 {
+                if let Some(progress) = &options.progress {
+                    progress.update(&file);
+                }
+            }
+
+            match file.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(err) => {
+                    return Err(ParseError::from(err)
+                        .at_line(line_number)
+                        .with_file(&bed.filename))
+                }
+            }
+
+            let x = match from_utf8(&line) {
+                Ok(x) => x.trim(),
+                Err(_) => {
+                    let err = ParseError::new(ParseErrorKind::InvalidUtf8)
+                        .at_line(line_number)
+                        .with_file(&bed.filename);
+                    options.mode.recover(err, &mut bed.warnings)?;
+                    continue;
+                }
+            };
+
+            if x.is_empty() || x.starts_with('#') || x.starts_with("browser") {
+                continue;
+            }
+
+            // Track lines past the first record are ignored, the file is taken as one track
+            if x.starts_with("track") {
+                if bed.is_empty() {
+                    let graph = bed.track.graph;
+                    bed.track = BedTrackLine::parse(x);
+                    bed.track.graph |= graph;
+                }
+                continue;
+            }
+
+            let parsed = if bed.track.graph {
+                parse_bedgraph_line(x).map(|(landmark, start, end, value)| {
+                    bed.signal
+                        .entry(landmark)
+                        .or_default()
+                        .push((start, end, value));
+                })
+            } else {
+                Feature::from_bed_line(x).map(|mut feature| {
+                    if !bed.track.item_rgb {
+                        feature.color = bed.track.color;
+                    }
+                    bed.features
+                        .entry(feature.landmark.clone())
+                        .or_default()
+                        .push(feature);
+                })
+            };
+
+            if let Err(err) = parsed {
+                let err = err.at_line(line_number).with_file(&bed.filename);
+                options.mode.recover(err, &mut bed.warnings)?;
+            }
+        }
+
+        if let Some(progress) = &options.progress {
+            progress.finish();
+        }
+
+        for features in bed.features.values_mut() {
+            features.sort_by_key(|x| (x.start, x.end));
+        }
+        for signal in bed.signal.values_mut() {
+            signal.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        }
+
+        bed.landmarks = bed.index_landmarks();
+        Ok(bed)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.features.is_empty() && self.signal.is_empty()
+    }
+
+    // Largest first, as with GFF3
+    fn index_landmarks(&self) -> Vec<Landmark> {
+        let features = self.features.iter().map(|(id, x)| {
+            let length = x.iter().map(|x| x.end).max().unwrap_or(0);
+            (id, x.len(), length)
+        });
+        let signal = self.signal.iter().map(|(id, x)| {
+            let length = x.iter().map(|x| x.1).max().unwrap_or(0);
+            (id, x.len(), length)
+        });
+
+        let mut landmarks = features
+            .chain(signal)
+            .map(|(id, count, length)| Landmark {
+                id: id.clone(),
+                offset: 0,
+                line: None,
+                data_length: count,
+                length,
+                features: count,
+            })
+            .collect::<Vec<Landmark>>();
+
+        landmarks.sort_by_key(|x| x.data_length);
+        landmarks.reverse();
+        landmarks
+    }
+
+    pub fn is_graph(&self) -> bool {
+        self.track.graph
+    }
+
+    /// Features of a landmark, empty for bedGraph
+    pub fn parse_region(&self, landmark: &str) -> Result<Vec<Feature>, ParseError> {
+        Ok(self.features.get(landmark).cloned().unwrap_or_default())
+    }
+
+    /// Signal overlapping [start, end), 0-based
+    pub fn signal(&self, landmark: &str, start: usize, end: usize) -> &[(usize, usize, f64)] {
+        let signal = match self.signal.get(landmark) {
+            Some(x) => x,
+            None => return &[],
+        };
+
+        // bedGraph intervals don't overlap, so ends are sorted too
+        let first = signal.partition_point(|x| x.1 <= start);
+        let last = signal.partition_point(|x| x.0 < end);
+        &signal[first..last.max(first)]
+    }
+}
+
+fn parse_rgb(x: &str) -> Option<[u8; 3]> {
+    let mut rgb = [0u8; 3];
+    let mut parts = x.split(',');
+    for channel in rgb.iter_mut() {
+        *channel = parts.next()?.trim().parse().ok()?;
+    }
+    Some(rgb)
+}
+
+// Columns are tab separated, though plenty of files use spaces
+fn columns(line: &str) -> Vec<&str> {
+    if line.contains('\t') {
+        line.split('\t').collect()
+    } else {
+        line.split_whitespace().collect()
+    }
+}
+
+fn parse_bedgraph_line(line: &str) -> Result<(String, usize, usize, f64), ParseError> {
+    let split = columns(line);
+    if split.len() < 4 {
+        return Err(ParseError::new(ParseErrorKind::MissingField)
+            .with_field(split.len() + 1, BEDGRAPH_COLUMNS[split.len()]));
+    }
+
+    let invalid = |kind: ParseErrorKind, n: usize| {
+        ParseError::new(kind).with_field(n + 1, BEDGRAPH_COLUMNS[n])
+    };
+    let coordinate = |n: usize| {
+        usize::from_str(split[n]).map_err(|_| invalid(ParseErrorKind::InvalidInteger, n))
+    };
+
+    let start = coordinate(1)?;
+    let end = coordinate(2)?;
+    if end < start {
+        return Err(invalid(ParseErrorKind::InvalidValue, 2));
+    }
+
+    let value = f64::from_str(split[3]).map_err(|_| invalid(ParseErrorKind::InvalidFloat, 3))?;
+
+    Ok((split[0].to_string(), start, end, value))
+}
+
+impl Feature {
+    /// BED records become features with exon children for their blocks and a CDS child for the
+    /// thick part, so they are drawn like transcripts
+    pub fn from_bed_line(line: &str) -> Result<Feature, ParseError> {
+        let split = columns(line);
+        if split.len() < 3 {
+            return Err(ParseError::new(ParseErrorKind::MissingField)
+                .with_field(split.len() + 1, BED_COLUMNS[split.len()]));
+        }
+
+        let invalid = |kind: ParseErrorKind, n: usize| {
+            ParseError::new(kind).with_field(n + 1, BED_COLUMNS[n])
+        };
+        let coordinate = |n: usize| {
+            usize::from_str(split[n]).map_err(|_| invalid(ParseErrorKind::InvalidInteger, n))
+        };
+        let list = |n: usize| {
+            split[n]
+                .split(',')
+                .filter(|x| !x.is_empty())
+                .map(|x| usize::from_str(x.trim()))
+                .collect::<Result<Vec<usize>, _>>()
+                .map_err(|_| invalid(ParseErrorKind::InvalidInteger, n))
+        };
+
+        let start = coordinate(1)?;
+        let end = coordinate(2)?;
+        if end < start {
+            return Err(invalid(ParseErrorKind::InvalidValue, 2));
+        }
+
+        let mut feature = Feature {
+            landmark: split[0].to_string(),
+            source: "BED".to_string(),
+            feature_type: "region".to_string(),
+            start: start + 1,
+            end,
+            ..Default::default()
+        };
+
+        if let Some(x) = split.get(3) {
+            feature.name = x.to_string();
+            feature.attributes.name = Some(x.to_string());
+        }
+
+        feature.score = match split.get(4) {
+            None | Some(&".") => None,
+            Some(x) => {
+                Some(f64::from_str(x).map_err(|_| invalid(ParseErrorKind::InvalidFloat, 4))?)
+            }
+        };
+
+        feature.strand = match split.get(5) {
+            None | Some(&".") => None,
+            Some(x) => Some(
+                x.parse::<Orientation>()
+                    .map_err(|_| invalid(ParseErrorKind::InvalidOrientation, 5))?,
+            ),
+        };
+
+        let thick = match (split.get(6), split.get(7)) {
+            (Some(_), Some(_)) => Some((coordinate(6)?, coordinate(7)?)),
+            (Some(_), None) => return Err(invalid(ParseErrorKind::MissingField, 7)),
+            _ => None,
+        };
+
+        feature.color = match split.get(8) {
+            None | Some(&"0") | Some(&".") => None,
+            Some(x) => Some(parse_rgb(x).ok_or_else(|| invalid(ParseErrorKind::InvalidValue, 8))?),
+        };
+
+        // Blocks are relative to chromStart, without them the whole feature is one
+        let blocks = if split.len() >= 12 {
+            let count = coordinate(9)?;
+            let sizes = list(10)?;
+            let starts = list(11)?;
+            if sizes.len() != count || starts.len() != count {
+                return Err(invalid(
+                    ParseErrorKind::LengthMismatch {
+                        expected: count,
+                        found: sizes.len().min(starts.len()),
+                    },
+                    10,
+                ));
+            }
+            starts
+                .into_iter()
+                .zip(sizes)
+                .map(|(block_start, size)| (start + block_start, start + block_start + size))
+                .collect::<Vec<(usize, usize)>>()
+        } else if split.len() > 9 {
+            return Err(invalid(ParseErrorKind::MissingField, split.len()));
+        } else {
+            vec![(start, end)]
+        };
+
+        // thickStart == thickEnd is how non-coding records say so
+        let thick = thick.filter(|x| x.0 < x.1);
+        if thick.is_none() && blocks.len() == 1 {
+            return Ok(feature);
+        }
+
+        let part = |feature_type: &str, (part_start, part_end): (usize, usize)| Feature {
+            landmark: feature.landmark.clone(),
+            source: feature.source.clone(),
+            feature_type: feature_type.to_string(),
+            start: part_start + 1,
+            end: part_end,
+            strand: feature.strand,
+            ..Default::default()
+        };
+
+        let mut parts = blocks
+            .into_iter()
+            .map(|x| part("exon", x))
+            .collect::<Vec<Feature>>();
+        if let Some(thick) = thick {
+            parts.push(part("CDS", thick));
+            feature.feature_type = "mRNA".to_string();
+        }
+
+        feature.subfeatures = Some(parts);
+        Ok(feature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::transcript::*;
+
+    #[test]
+    fn test_from_bed_line() {
+        let feature = Feature::from_bed_line("chr1\t99\t200").unwrap();
+        assert_eq!((feature.start, feature.end), (100, 200));
+        assert!(feature.subfeatures.is_none());
+
+        let feature = Feature::from_bed_line(
+            "chr1\t1000\t5000\tuc001\t960\t-\t1200\t4900\t255,0,0\t2\t567,1488,\t0,2512,",
+        )
+        .unwrap();
+        assert_eq!(feature.name, "uc001");
+        assert_eq!(feature.score, Some(960.0));
+        assert_eq!(feature.strand, Some(Orientation::Negative));
+        assert_eq!(feature.color, Some([255, 0, 0]));
+
+        let model = TranscriptModel::new(&feature);
+        assert_eq!(
+            model.blocks,
+            vec![
+                (1000, 1200, BlockKind::Utr),
+                (1200, 1567, BlockKind::Cds),
+                (3512, 4900, BlockKind::Cds),
+                (4900, 5000, BlockKind::Utr),
+            ]
+        );
+        assert_eq!(model.introns, vec![(1567, 3512)]);
+
+        // Non-coding, thickStart == thickEnd
+        let feature = Feature::from_bed_line("chr1 10 50 nc 0 + 10 10 0 2 10,10 0,30").unwrap();
+        let model = TranscriptModel::new(&feature);
+        assert_eq!(
+            model.blocks,
+            vec![(10, 20, BlockKind::Exon), (40, 50, BlockKind::Exon)]
+        );
+
+        assert!(Feature::from_bed_line("chr1\t10").is_err());
+        assert!(Feature::from_bed_line("chr1\t50\t10").is_err());
+        assert!(Feature::from_bed_line("chr1\t10\t50\tx\t0\t+\t10\t50\t0\t2\t10\t0,30").is_err());
+    }
+
+    #[test]
+    fn test_parse_bed() {
+        let bed = Bed::parse("test_data/test.bed").unwrap();
+        assert_eq!(bed.track.name.as_deref(), Some("Peaks"));
+        assert!(bed.track.item_rgb);
+        assert!(!bed.is_graph());
+        assert_eq!(bed.landmarks[0].id, "chr1");
+        assert_eq!(bed.parse_region("chr1").unwrap().len(), 3);
+        assert_eq!(bed.warnings.errors.len(), 0);
+
+        let bed = Bed::parse("test_data/test.bedgraph").unwrap();
+        assert!(bed.is_graph());
+        assert_eq!(bed.track.color, Some([0, 0, 255]));
+        assert!(bed.parse_region("chr1").unwrap().is_empty());
+        assert_eq!(
+            bed.signal("chr1", 150, 350),
+            &[(100, 200, 1.5), (200, 300, 2.0), (300, 400, 0.5)]
+        );
+        assert!(bed.signal("chr1", 400, 500).is_empty());
+        assert!(bed.signal("chr2", 0, 500).is_empty());
+
+        assert!(parse_bedgraph_line("chr1\t100\t200\t1").is_ok());
+        let error = parse_bedgraph_line("chr1\t200\t100\t1").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidValue);
+        assert_eq!(error.column, Some(3));
+    }
+}
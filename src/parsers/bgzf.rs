@@ -0,0 +1,494 @@
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use flate2::Crc;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+// gzip magic, deflate, FEXTRA set
+const GZIP_MAGIC: [u8; 3] = [31, 139, 8];
+const FEXTRA: u8 = 4;
+const BLOCK_HEADER_LENGTH: usize = 18;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip, // Plain gzip, can only be read from the start
+    Bgzf, // Blocked gzip, random access through virtual offsets
+}
+
+/// Sniffs the first bytes, the file extension doesn't matter
+pub fn detect_compression(filename: &str) -> io::Result<Compression> {
+    let mut header = [0; BLOCK_HEADER_LENGTH];
+    let mut file = File::open(filename)?;
+    let mut read = 0;
+
+    while read < header.len() {
+        match file.read(&mut header[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+
+    Ok(compression_of(&header[..read]))
+}
+
+fn compression_of(header: &[u8]) -> Compression {
+    if header.len() < 3 || header[..3] != GZIP_MAGIC {
+        return Compression::None;
+    }
+
+    // BGZF is gzip with a BC extra subfield holding the block size
+    if header.len() >= BLOCK_HEADER_LENGTH
+        && header[3] & FEXTRA != 0
+        && header[12] == b'B'
+        && header[13] == b'C'
+        && header[14] == 2
+    {
+        Compression::Bgzf
+    } else {
+        Compression::Gzip
+    }
+}
+
+// Compressed offset of a block in the upper 48 bits, offset into the decompressed block in the
+// lower 16
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VirtualOffset(pub u64);
+
+impl VirtualOffset {
+    pub fn new(compressed: u64, uncompressed: u16) -> Self {
+        VirtualOffset(compressed << 16 | uncompressed as u64)
+    }
+
+    pub fn compressed(&self) -> u64 {
+        self.0 >> 16
+    }
+
+    pub fn uncompressed(&self) -> u16 {
+        (self.0 & 0xffff) as u16
+    }
+}
+
+pub struct BgzfReader<R> {
+    inner: R,
+    block_offset: u64,      // Compressed offset of the current block
+    next_block_offset: u64, // Compressed offset of the block after it
+    data: Vec<u8>,
+    position: usize, // Position in data
+}
+
+impl<R: Read + Seek> BgzfReader<R> {
+    pub fn new(inner: R) -> Self {
+        BgzfReader {
+            inner,
+            block_offset: 0,
+            next_block_offset: 0,
+            data: Vec::with_capacity(65536),
+            position: 0,
+        }
+    }
+
+    pub fn virtual_position(&self) -> VirtualOffset {
+        // A finished block is the same place as the start of the next one, this keeps offsets
+        // comparable with the ones written in tabix indexes
+        if self.position >= self.data.len() {
+            VirtualOffset::new(self.next_block_offset, 0)
+        } else {
+            VirtualOffset::new(self.block_offset, self.position as u16)
+        }
+    }
+
+    pub fn seek_virtual(&mut self, offset: VirtualOffset) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(offset.compressed()))?;
+        self.next_block_offset = offset.compressed();
+        self.data.clear();
+        self.position = 0;
+
+        if offset.uncompressed() > 0 {
+            self.read_block()?;
+            if offset.uncompressed() as usize > self.data.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "virtual offset past the end of its block",
+                ));
+            }
+            self.position = offset.uncompressed() as usize;
+        }
+
+        Ok(())
+    }
+
+    // Reads the next block into data, which is left empty at EOF
+    fn read_block(&mut self) -> io::Result<()> {
+        self.block_offset = self.next_block_offset;
+        self.data.clear();
+        self.position = 0;
+
+        let mut header = [0; BLOCK_HEADER_LENGTH];
+        match read_fully(&mut self.inner, &mut header)? {
+            0 => return Ok(()),
+            BLOCK_HEADER_LENGTH => (),
+            _ => return Err(invalid_data("truncated BGZF block header")),
+        }
+
+        if compression_of(&header) != Compression::Bgzf {
+            return Err(invalid_data("not a BGZF block"));
+        }
+
+        let xlen = u16::from_le_bytes([header[10], header[11]]) as usize;
+        let block_size = u16::from_le_bytes([header[16], header[17]]) as usize + 1;
+
+        if block_size < BLOCK_HEADER_LENGTH + 8 || xlen < 6 {
+            return Err(invalid_data("invalid BGZF block size"));
+        }
+
+        // Any extra subfields after BC, the deflated data, then CRC32 and ISIZE
+        let mut rest = vec![0; block_size - BLOCK_HEADER_LENGTH];
+        if read_fully(&mut self.inner, &mut rest)? != rest.len() {
+            return Err(invalid_data("truncated BGZF block"));
+        }
+        if xlen - 6 > rest.len() - 8 {
+            return Err(invalid_data("invalid BGZF extra field length"));
+        }
+
+        let cdata = &rest[xlen - 6..rest.len() - 8];
+        let footer = &rest[rest.len() - 8..];
+        let crc = u32::from_le_bytes([footer[0], footer[1], footer[2], footer[3]]);
+        let isize = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]) as usize;
+
+        self.data.reserve(isize);
+        DeflateDecoder::new(cdata).read_to_end(&mut self.data)?;
+
+        let mut check = Crc::new();
+        check.update(&self.data);
+        if self.data.len() != isize || check.sum() != crc {
+            return Err(invalid_data("BGZF block failed its checksum"));
+        }
+
+        self.next_block_offset = self.block_offset + block_size as u64;
+
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for BgzfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = std::cmp::min(available.len(), buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> BufRead for BgzfReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // Empty blocks (such as the EOF marker) are skipped
+        while self.position >= self.data.len() {
+            let before = self.next_block_offset;
+            self.read_block()?;
+            if self.data.is_empty() && self.next_block_offset == before {
+                break;
+            }
+        }
+
+        Ok(&self.data[self.position..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.position = std::cmp::min(self.position + amt, self.data.len());
+    }
+}
+
+fn read_fully<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(read)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+enum Reader {
+    Plain(BufReader<File>),
+    Gzip(BufReader<MultiGzDecoder<File>>),
+    Bgzf(BgzfReader<BufReader<File>>),
+}
+
+/// Any input file, compressed or not. Positions are opaque: byte offsets for plain files,
+/// virtual offsets for BGZF, and decompressed offsets for gzip (where seeking means reading
+/// from the start again).
+pub struct InputFile {
+    filename: String,
+    compression: Compression,
+    reader: Reader,
+    position: u64, // Only tracked for plain and gzip
+}
+
+impl InputFile {
+    pub fn open(filename: &str) -> io::Result<InputFile> {
+        let compression = detect_compression(filename)?;
+        let file = File::open(filename)?;
+
+        let reader = match compression {
+            Compression::None => Reader::Plain(BufReader::new(file)),
+            Compression::Gzip => Reader::Gzip(BufReader::new(MultiGzDecoder::new(file))),
+            Compression::Bgzf => Reader::Bgzf(BgzfReader::new(BufReader::new(file))),
+        };
+
+        Ok(InputFile {
+            filename: filename.to_string(),
+            compression,
+            reader,
+            position: 0,
+        })
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    pub fn position(&self) -> u64 {
+        match &self.reader {
+            Reader::Bgzf(x) => x.virtual_position().0,
+            _ => self.position,
+        }
+    }
+
+    /// Bytes of the file itself read so far, compressed or not, for progress against its size.
+    /// Runs a little ahead of what has been consumed for gzip.
+    pub fn compressed_position(&self) -> u64 {
+        match &self.reader {
+            Reader::Plain(_) => self.position,
+            Reader::Bgzf(x) => x.virtual_position().compressed(),
+            Reader::Gzip(x) => {
+                let mut file: &File = x.get_ref().get_ref();
+                file.stream_position().unwrap_or(0)
+            }
+        }
+    }
+
+    pub fn seek_to(&mut self, position: u64) -> io::Result<()> {
+        match &mut self.reader {
+            Reader::Plain(x) => {
+                x.seek(SeekFrom::Start(position))?;
+            }
+            Reader::Bgzf(x) => x.seek_virtual(VirtualOffset(position))?,
+            Reader::Gzip(_) => {
+                let file = File::open(&self.filename)?;
+                let mut reader = BufReader::new(MultiGzDecoder::new(file));
+                io::copy(&mut (&mut reader).take(position), &mut io::sink())?;
+                self.reader = Reader::Gzip(reader);
+            }
+        }
+
+        self.position = position;
+        Ok(())
+    }
+}
+
+impl Read for InputFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = std::cmp::min(available.len(), buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for InputFile {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match &mut self.reader {
+            Reader::Plain(x) => x.fill_buf(),
+            Reader::Gzip(x) => x.fill_buf(),
+            Reader::Bgzf(x) => x.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.position += amt as u64;
+        match &mut self.reader {
+            Reader::Plain(x) => x.consume(amt),
+            Reader::Gzip(x) => x.consume(amt),
+            Reader::Bgzf(x) => x.consume(amt),
+        }
+    }
+}
+
+/// bgzip's .gzi: where each block starts, compressed and decompressed. Lets a decompressed
+/// offset (like those in a .fai) be turned into a virtual offset.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GziIndex {
+    pub blocks: Vec<(u64, u64)>, // Compressed, decompressed. The first block at (0, 0) is implied
+}
+
+impl GziIndex {
+    pub fn read(filename: &str) -> io::Result<GziIndex> {
+        let mut bytes = Vec::new();
+        File::open(filename)?.read_to_end(&mut bytes)?;
+
+        let u64_at = |n: usize| -> io::Result<u64> {
+            bytes
+                .get(n..n + 8)
+                .map(|x| u64::from_le_bytes(x.try_into().unwrap()))
+                .ok_or_else(|| invalid_data("truncated .gzi"))
+        };
+
+        let count = u64_at(0)? as usize;
+        let mut blocks = Vec::with_capacity(count);
+        for i in 0..count {
+            blocks.push((u64_at(8 + i * 16)?, u64_at(16 + i * 16)?));
+        }
+
+        Ok(GziIndex { blocks })
+    }
+
+    /// Walks the block headers, only the last 4 bytes of each block (ISIZE) are needed
+    pub fn build(filename: &str) -> io::Result<GziIndex> {
+        let mut file = BufReader::new(File::open(filename)?);
+        let mut blocks = Vec::new();
+        let mut compressed = 0;
+        let mut decompressed = 0;
+
+        loop {
+            let mut header = [0; BLOCK_HEADER_LENGTH];
+            match read_fully(&mut file, &mut header)? {
+                0 => break,
+                BLOCK_HEADER_LENGTH => (),
+                _ => return Err(invalid_data("truncated BGZF block header")),
+            }
+
+            if compression_of(&header) != Compression::Bgzf {
+                return Err(invalid_data("not a BGZF block"));
+            }
+
+            let block_size = u16::from_le_bytes([header[16], header[17]]) as u64 + 1;
+            file.seek(SeekFrom::Current(
+                block_size as i64 - BLOCK_HEADER_LENGTH as i64 - 4,
+            ))?;
+
+            let mut isize = [0; 4];
+            file.read_exact(&mut isize)?;
+
+            if compressed > 0 {
+                blocks.push((compressed, decompressed));
+            }
+
+            compressed += block_size;
+            decompressed += u32::from_le_bytes(isize) as u64;
+        }
+
+        // bgzip doesn't list the empty EOF block
+        if let Some(&(_, x)) = blocks.last() {
+            if x == decompressed {
+                blocks.pop();
+            }
+        }
+
+        Ok(GziIndex { blocks })
+    }
+
+    pub fn write(&self, filename: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(filename)?);
+        out.write_all(&(self.blocks.len() as u64).to_le_bytes())?;
+        for (compressed, decompressed) in self.blocks.iter() {
+            out.write_all(&compressed.to_le_bytes())?;
+            out.write_all(&decompressed.to_le_bytes())?;
+        }
+        out.flush()
+    }
+
+    pub fn virtual_offset(&self, position: u64) -> VirtualOffset {
+        let block = match self.blocks.partition_point(|x| x.1 <= position) {
+            0 => (0, 0),
+            n => self.blocks[n - 1],
+        };
+
+        VirtualOffset::new(block.0, (position - block.1) as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_compression() {
+        assert_eq!(
+            detect_compression("test_data/test.fa").unwrap(),
+            Compression::None
+        );
+        assert_eq!(
+            detect_compression("test_data/test.fa.gz").unwrap(),
+            Compression::Bgzf
+        );
+        assert_eq!(
+            detect_compression("test_data/test.gtf.gz").unwrap(),
+            Compression::Gzip
+        );
+    }
+
+    #[test]
+    fn test_virtual_offsets() {
+        let mut plain = String::new();
+        File::open("test_data/sorted.gff3")
+            .unwrap()
+            .read_to_string(&mut plain)
+            .unwrap();
+
+        let mut input = InputFile::open("test_data/sorted.gff3.gz").unwrap();
+        let mut line = String::new();
+        input.read_line(&mut line).unwrap();
+        let second = input.position();
+
+        let mut decompressed = line.clone();
+        input.read_to_string(&mut decompressed).unwrap();
+        assert_eq!(decompressed, plain);
+
+        // Back to the second line, which is in a later block
+        input.seek_to(second).unwrap();
+        let mut again = String::new();
+        input.read_line(&mut again).unwrap();
+        assert_eq!(again, plain.lines().nth(1).unwrap().to_string() + "\n");
+        assert!(VirtualOffset(second).compressed() > 0);
+    }
+
+    #[test]
+    fn test_gzi() {
+        let gzi = GziIndex::build("test_data/test.fa.gz").unwrap();
+        assert_eq!(gzi, GziIndex::read("test_data/test.fa.gz.gzi").unwrap());
+        assert!(!gzi.blocks.is_empty());
+
+        let (compressed, decompressed) = gzi.blocks[0];
+        assert_eq!(
+            gzi.virtual_offset(decompressed + 2),
+            VirtualOffset::new(compressed, 2)
+        );
+        assert_eq!(gzi.virtual_offset(3), VirtualOffset::new(0, 3));
+    }
+
+    #[test]
+    fn test_malformed_block() {
+        let mut block = Vec::new();
+        File::open("test_data/test.fa.gz")
+            .unwrap()
+            .read_to_end(&mut block)
+            .unwrap();
+
+        // Extra fields longer than the whole block
+        block[10..12].copy_from_slice(&u16::MAX.to_le_bytes());
+        let mut reader = BgzfReader::new(io::Cursor::new(block));
+        let mut buf = [0; 16];
+        let error = reader.read(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
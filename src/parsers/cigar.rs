@@ -0,0 +1,156 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CigarOp {
+    Match,     // M
+    Insertion, // I
+    Deletion,  // D
+    Skip,      // N
+    SoftClip,  // S
+    HardClip,  // H
+    Padding,   // P
+    Equal,     // =
+    Mismatch,  // X
+}
+
+impl CigarOp {
+    pub fn from_char(c: char) -> Option<CigarOp> {
+        match c {
+            'M' => Some(CigarOp::Match),
+            'I' => Some(CigarOp::Insertion),
+            'D' => Some(CigarOp::Deletion),
+            'N' => Some(CigarOp::Skip),
+            'S' => Some(CigarOp::SoftClip),
+            'H' => Some(CigarOp::HardClip),
+            'P' => Some(CigarOp::Padding),
+            '=' => Some(CigarOp::Equal),
+            'X' => Some(CigarOp::Mismatch),
+            _ => None,
+        }
+    }
+
+    pub fn as_char(&self) -> char {
+        match self {
+            CigarOp::Match => 'M',
+            CigarOp::Insertion => 'I',
+            CigarOp::Deletion => 'D',
+            CigarOp::Skip => 'N',
+            CigarOp::SoftClip => 'S',
+            CigarOp::HardClip => 'H',
+            CigarOp::Padding => 'P',
+            CigarOp::Equal => '=',
+            CigarOp::Mismatch => 'X',
+        }
+    }
+
+    // In GFA overlaps the first segment is the reference, the second the query
+    pub fn consumes_reference(&self) -> bool {
+        matches!(
+            self,
+            CigarOp::Match | CigarOp::Deletion | CigarOp::Skip | CigarOp::Equal | CigarOp::Mismatch
+        )
+    }
+
+    pub fn consumes_query(&self) -> bool {
+        matches!(
+            self,
+            CigarOp::Match
+                | CigarOp::Insertion
+                | CigarOp::SoftClip
+                | CigarOp::Equal
+                | CigarOp::Mismatch
+        )
+    }
+}
+
+/// An overlap or alignment, 10M2I5M
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cigar(pub Vec<(usize, CigarOp)>);
+
+impl Cigar {
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|(count, _)| *count == 0)
+    }
+
+    /// Bases of the first segment (the end of a link's from) covered by the overlap
+    pub fn reference_length(&self) -> usize {
+        self.0
+            .iter()
+            .filter(|(_, op)| op.consumes_reference())
+            .map(|(count, _)| count)
+            .sum()
+    }
+
+    /// Bases of the second segment (the start of a link's to) covered by the overlap
+    pub fn query_length(&self) -> usize {
+        self.0
+            .iter()
+            .filter(|(_, op)| op.consumes_query())
+            .map(|(count, _)| count)
+            .sum()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidCigar;
+
+impl FromStr for Cigar {
+    type Err = InvalidCigar;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ops = Vec::new();
+        let mut count: Option<usize> = None;
+
+        for c in s.chars() {
+            if let Some(digit) = c.to_digit(10) {
+                let x = count.unwrap_or(0);
+                count = Some(
+                    x.checked_mul(10)
+                        .and_then(|x| x.checked_add(digit as usize))
+                        .ok_or(InvalidCigar)?,
+                );
+            } else {
+                let op = CigarOp::from_char(c).ok_or(InvalidCigar)?;
+                ops.push((count.take().ok_or(InvalidCigar)?, op));
+            }
+        }
+
+        // Trailing count without an operation, or nothing at all
+        if count.is_some() || ops.is_empty() {
+            return Err(InvalidCigar);
+        }
+
+        Ok(Cigar(ops))
+    }
+}
+
+impl fmt::Display for Cigar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (count, op) in self.0.iter() {
+            write!(f, "{}{}", count, op.as_char())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cigar() {
+        let cigar = "10M2I3D1=".parse::<Cigar>().unwrap();
+        assert_eq!(cigar.0.len(), 4);
+        assert_eq!(cigar.0[1], (2, CigarOp::Insertion));
+        assert_eq!(cigar.reference_length(), 14);
+        assert_eq!(cigar.query_length(), 13);
+        assert_eq!(cigar.to_string(), "10M2I3D1=");
+
+        assert!("0M".parse::<Cigar>().unwrap().is_empty());
+
+        for x in ["", "*", "M", "10", "10Q", "1,2,3"] {
+            assert!(x.parse::<Cigar>().is_err(), "{}", x);
+        }
+    }
+}
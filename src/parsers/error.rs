@@ -0,0 +1,207 @@
+use std::fmt;
+
+use super::progress::Progress;
+use super::sequence::SequenceStorage;
+
+// Only the first few warnings are kept, the rest are counted
+const MAX_WARNINGS: usize = 1000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    Io(String),
+    InvalidUtf8,
+    MissingField,
+    InvalidInteger,
+    InvalidFloat,
+    InvalidOrientation,
+    InvalidValue,
+    LengthMismatch { expected: usize, found: usize },
+    UnknownSequence(String),
+    InvalidIndex(String),
+    UnknownFormat,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::Io(err) => write!(f, "I/O error: {}", err),
+            ParseErrorKind::InvalidUtf8 => write!(f, "invalid UTF-8"),
+            ParseErrorKind::MissingField => write!(f, "missing field"),
+            ParseErrorKind::InvalidInteger => write!(f, "invalid integer"),
+            ParseErrorKind::InvalidFloat => write!(f, "invalid number"),
+            ParseErrorKind::InvalidOrientation => write!(f, "invalid orientation"),
+            ParseErrorKind::InvalidValue => write!(f, "invalid value"),
+            ParseErrorKind::LengthMismatch { expected, found } => {
+                write!(
+                    f,
+                    "length mismatch (expected {}, found {})",
+                    expected, found
+                )
+            }
+            ParseErrorKind::UnknownSequence(name) => write!(f, "unknown sequence {}", name),
+            ParseErrorKind::InvalidIndex(err) => write!(f, "invalid index: {}", err),
+            ParseErrorKind::UnknownFormat => write!(f, "unknown file format"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>, // 1-based, tab-separated column
+    pub field: Option<String>,
+    pub kind: ParseErrorKind,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind) -> Self {
+        ParseError {
+            file: None,
+            line: None,
+            column: None,
+            field: None,
+            kind,
+        }
+    }
+
+    pub fn with_file(mut self, file: &str) -> Self {
+        self.file = Some(file.to_string());
+        self
+    }
+
+    pub fn at_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    pub fn with_field(mut self, column: usize, field: &str) -> Self {
+        self.column = Some(column);
+        self.field = Some(field.to_string());
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.as_deref().unwrap_or("<unknown>"))?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        write!(f, ": {}", self.kind)?;
+        if let Some(field) = &self.field {
+            write!(f, " in field {}", field)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<std::io::Error> for ParseError {
+    fn from(err: std::io::Error) -> Self {
+        ParseError::new(ParseErrorKind::Io(err.to_string()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseMode {
+    Strict,  // Stop at the first malformed record
+    Lenient, // Skip malformed records, collecting warnings
+}
+
+impl Default for ParseMode {
+    fn default() -> Self {
+        ParseMode::Strict
+    }
+}
+
+impl ParseMode {
+    /// Strict mode hands the error back, lenient mode records it so the caller can skip the record
+    pub fn recover(&self, err: ParseError, warnings: &mut Warnings) -> Result<(), ParseError> {
+        match self {
+            ParseMode::Strict => Err(err),
+            ParseMode::Lenient => {
+                warnings.push(err);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+    pub mode: ParseMode,
+    pub sequences: SequenceStorage, // What to keep of GFA segment sequences
+    pub progress: Option<Progress>, // Updated as the file is read
+}
+
+impl ParseOptions {
+    pub fn lenient() -> Self {
+        ParseOptions {
+            mode: ParseMode::Lenient,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Warnings {
+    pub errors: Vec<ParseError>,
+    pub skipped: usize, // Total number of skipped records, including those not kept in errors
+}
+
+impl Warnings {
+    pub fn push(&mut self, err: ParseError) {
+        self.skipped += 1;
+        if self.errors.len() < MAX_WARNINGS {
+            self.errors.push(err);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.skipped == 0
+    }
+
+    pub fn append(&mut self, other: Warnings) {
+        let skipped = self.skipped + other.skipped;
+        for err in other.errors {
+            self.push(err);
+        }
+        self.skipped = skipped;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let err = ParseError::new(ParseErrorKind::InvalidInteger)
+            .with_field(4, "start")
+            .at_line(12)
+            .with_file("test.gff3");
+        assert_eq!(
+            err.to_string(),
+            "test.gff3:12:4: invalid integer in field start"
+        );
+    }
+
+    #[test]
+    fn test_recover() {
+        let mut warnings = Warnings::default();
+        let err = ParseError::new(ParseErrorKind::MissingField);
+
+        assert!(ParseMode::Strict
+            .recover(err.clone(), &mut warnings)
+            .is_err());
+        assert!(warnings.is_empty());
+
+        assert!(ParseMode::Lenient.recover(err, &mut warnings).is_ok());
+        assert_eq!(warnings.skipped, 1);
+    }
+}
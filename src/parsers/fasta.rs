@@ -0,0 +1,412 @@
+use simdutf8::basic::from_utf8;
+use twox_hash::RandomXxh3HashBuilder64;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+use super::bgzf::*;
+use super::error::*;
+
+// One line of a samtools .fai: NAME LENGTH OFFSET LINEBASES LINEWIDTH
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FaiRecord {
+    pub name: String,
+    pub length: usize,
+    pub offset: usize,     // Byte offset of the first base
+    pub line_bases: usize, // Bases per line
+    pub line_bytes: usize, // Bytes per line, including the newline
+}
+
+impl FaiRecord {
+    // Byte offset of a 0-based position
+    fn position(&self, pos: usize) -> usize {
+        self.offset + (pos / self.line_bases) * self.line_bytes + pos % self.line_bases
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Fasta {
+    pub filename: String,
+    pub index: Vec<FaiRecord>,
+    pub index_atlas: HashMap<String, usize, RandomXxh3HashBuilder64>,
+    pub compression: Compression,
+    pub gzi: Option<GziIndex>, // Only for BGZF, turns .fai offsets into virtual offsets
+}
+
+impl Fasta {
+    /// Uses filename.fai (and filename.gzi for bgzipped files) when present, otherwise the
+    /// indexes are built and written next to the FASTA for the next time (if the directory is
+    /// writable)
+    pub fn open<T>(filename: T) -> Result<Fasta, ParseError>
+    where
+        T: ToString,
+    {
+        let filename = filename.to_string();
+        let fai = format!("{}.fai", filename);
+        let gzi = format!("{}.gzi", filename);
+
+        let compression = match detect_compression(&filename) {
+            Ok(x) => x,
+            Err(err) => return Err(ParseError::from(err).with_file(&filename)),
+        };
+
+        let gzi = match compression {
+            Compression::Bgzf if std::path::Path::new(&gzi).exists() => Some(GziIndex::read(&gzi)),
+            Compression::Bgzf => {
+                let index = GziIndex::build(&filename);
+                if let Err(err) = index.as_ref().map(|x| x.write(&gzi)) {
+                    println!("Unable to write {}: {}", gzi, err);
+                }
+                Some(index)
+            }
+            _ => None,
+        };
+
+        let gzi = match gzi.transpose() {
+            Ok(x) => x,
+            Err(err) => return Err(ParseError::from(err).with_file(&filename)),
+        };
+
+        let index = if std::path::Path::new(&fai).exists() {
+            read_fai(&fai)?
+        } else {
+            let index = build_fai(&filename)?;
+            if let Err(err) = write_fai(&fai, &index) {
+                println!("Unable to write {}: {}", fai, err);
+            }
+            index
+        };
+
+        let mut fasta = Fasta::from_index(filename, index);
+        fasta.compression = compression;
+        fasta.gzi = gzi;
+        Ok(fasta)
+    }
+
+    pub fn from_index(filename: String, index: Vec<FaiRecord>) -> Fasta {
+        let index_atlas = index
+            .iter()
+            .enumerate()
+            .map(|(n, x)| (x.name.clone(), n))
+            .collect();
+
+        Fasta {
+            filename,
+            index,
+            index_atlas,
+            compression: Compression::None,
+            gzi: None,
+        }
+    }
+
+    pub fn get(&self, landmark: &str) -> Option<&FaiRecord> {
+        self.index_atlas.get(landmark).map(|&n| &self.index[n])
+    }
+
+    pub fn length(&self, landmark: &str) -> Option<usize> {
+        self.get(landmark).map(|x| x.length)
+    }
+
+    /// Bases of landmark in [start, end), 0-based. The end is clamped to the sequence length.
+    pub fn fetch(&self, landmark: &str, start: usize, end: usize) -> Result<Vec<u8>, ParseError> {
+        let record = match self.get(landmark) {
+            Some(x) => x,
+            None => {
+                return Err(
+                    ParseError::new(ParseErrorKind::UnknownSequence(landmark.to_string()))
+                        .with_file(&self.filename),
+                )
+            }
+        };
+
+        let end = std::cmp::min(end, record.length);
+        if start >= end {
+            return Ok(Vec::new());
+        }
+
+        // Everything in between, newlines included, then the newlines are dropped
+        let from = record.position(start);
+        let to = record.position(end - 1) + 1;
+
+        let position = match &self.gzi {
+            Some(gzi) => gzi.virtual_offset(from as u64).0,
+            None => from as u64,
+        };
+
+        let mut buffer = vec![0; to - from];
+        let read = InputFile::open(&self.filename).and_then(|mut file| {
+            file.seek_to(position)?;
+            file.read_exact(&mut buffer)
+        });
+        if let Err(err) = read {
+            return Err(ParseError::from(err).with_file(&self.filename));
+        }
+
+        buffer.retain(|&x| x != b'\n' && x != b'\r');
+
+        if buffer.len() != end - start {
+            return Err(ParseError::new(ParseErrorKind::LengthMismatch {
+                expected: end - start,
+                found: buffer.len(),
+            })
+            .with_file(&self.filename));
+        }
+
+        Ok(buffer)
+    }
+}
+
+pub fn read_fai(filename: &str) -> Result<Vec<FaiRecord>, ParseError> {
+    let file = match File::open(filename) {
+        Ok(x) => BufReader::new(x),
+        Err(err) => return Err(ParseError::from(err).with_file(filename)),
+    };
+
+    let mut index = Vec::new();
+
+    for (n, line) in file.lines().enumerate() {
+        let line_number = n + 1;
+        let line = match line {
+            Ok(x) => x,
+            Err(err) => {
+                return Err(ParseError::from(err)
+                    .at_line(line_number)
+                    .with_file(filename))
+            }
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let split = line.split('\t').collect::<Vec<&str>>();
+        if split.len() < 5 {
+            return Err(ParseError::new(ParseErrorKind::MissingField)
+                .with_field(split.len() + 1, FAI_COLUMNS[split.len()])
+                .at_line(line_number)
+                .with_file(filename));
+        }
+
+        let integer = |column: usize| {
+            split[column].parse::<usize>().map_err(|_| {
+                ParseError::new(ParseErrorKind::InvalidInteger)
+                    .with_field(column + 1, FAI_COLUMNS[column])
+                    .at_line(line_number)
+                    .with_file(filename)
+            })
+        };
+
+        let record = FaiRecord {
+            name: split[0].to_string(),
+            length: integer(1)?,
+            offset: integer(2)?,
+            line_bases: integer(3)?,
+            line_bytes: integer(4)?,
+        };
+
+        // Positions are found by dividing by the line length
+        if record.length > 0 && (record.line_bases == 0 || record.line_bytes < record.line_bases) {
+            return Err(ParseError::new(ParseErrorKind::InvalidIndex(format!(
+                "{} has {} bases in lines of {} bytes",
+                record.name, record.line_bases, record.line_bytes
+            )))
+            .at_line(line_number)
+            .with_file(filename));
+        }
+
+        index.push(record);
+    }
+
+    Ok(index)
+}
+
+const FAI_COLUMNS: [&str; 5] = ["name", "length", "offset", "linebases", "linewidth"];
+
+pub fn write_fai(filename: &str, index: &[FaiRecord]) -> Result<(), std::io::Error> {
+    let mut out = BufWriter::new(File::create(filename)?);
+    for x in index {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}",
+            x.name, x.length, x.offset, x.line_bases, x.line_bytes
+        )?;
+    }
+    out.flush()
+}
+
+/// Writes one record, wrapped at width bases per line
+pub fn write_fasta_record<W: Write>(
+    out: &mut W,
+    name: &str,
+    sequence: &[u8],
+    width: usize,
+) -> Result<(), std::io::Error> {
+    writeln!(out, ">{}", name)?;
+    for line in sequence.chunks(width) {
+        out.write_all(line)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Same rules as samtools faidx: every line of a sequence but the last has to be the same length.
+/// Offsets are into the decompressed data for compressed files.
+pub fn build_fai(filename: &str) -> Result<Vec<FaiRecord>, ParseError> {
+    let mut file = match InputFile::open(filename) {
+        Ok(x) => x,
+        Err(err) => return Err(ParseError::from(err).with_file(filename)),
+    };
+
+    let mut index: Vec<FaiRecord> = Vec::new();
+    let mut line: Vec<u8> = Vec::with_capacity(8192);
+    let mut offset: usize = 0;
+    let mut line_number: usize = 0;
+
+    // Set once a sequence has a line shorter than the first, any more bases after it are an error
+    let mut short_line = false;
+
+    loop {
+        line.clear();
+        line_number += 1;
+
+        let bytes_read = match file.read_until(b'\n', &mut line) {
+            Ok(x) => x,
+            Err(err) => {
+                return Err(ParseError::from(err)
+                    .at_line(line_number)
+                    .with_file(filename))
+            }
+        };
+
+        // EOF
+        if bytes_read == 0 {
+            break;
+        }
+
+        offset += bytes_read;
+
+        if line[0] == b'>' {
+            let name = match from_utf8(&line[1..]) {
+                Ok(x) => x.split_whitespace().next().unwrap_or_default(),
+                Err(_) => {
+                    return Err(ParseError::new(ParseErrorKind::InvalidUtf8)
+                        .at_line(line_number)
+                        .with_file(filename))
+                }
+            };
+
+            index.push(FaiRecord {
+                name: name.to_string(),
+                length: 0,
+                offset,
+                line_bases: 0,
+                line_bytes: 0,
+            });
+            short_line = false;
+            continue;
+        }
+
+        let line_bytes = bytes_read;
+        let line_bases = line
+            .iter()
+            .rev()
+            .skip_while(|&&x| x == b'\n' || x == b'\r')
+            .count();
+
+        let record = match index.last_mut() {
+            Some(x) => x,
+            None if line_bases == 0 => continue,
+            None => {
+                return Err(ParseError::new(ParseErrorKind::InvalidValue)
+                    .with_field(1, "header")
+                    .at_line(line_number)
+                    .with_file(filename))
+            }
+        };
+
+        // Blank lines are only allowed at the end of a sequence
+        if line_bases == 0 {
+            short_line = true;
+            continue;
+        }
+
+        if record.line_bases == 0 {
+            record.line_bases = line_bases;
+            record.line_bytes = line_bytes;
+        } else if short_line || line_bases > record.line_bases {
+            return Err(ParseError::new(ParseErrorKind::LengthMismatch {
+                expected: record.line_bases,
+                found: line_bases,
+            })
+            .at_line(line_number)
+            .with_file(filename));
+        }
+
+        if line_bases < record.line_bases || line_bytes != record.line_bytes {
+            short_line = true;
+        }
+
+        record.length += line_bases;
+    }
+
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_fai() {
+        let index = build_fai("test_data/test.fa").unwrap();
+        assert_eq!(index, read_fai("test_data/test.fa.fai").unwrap());
+
+        assert_eq!(index.len(), 2);
+        assert_eq!(index[0].name, "chr1");
+        assert_eq!(index[0].length, 35);
+        assert_eq!((index[0].line_bases, index[0].line_bytes), (10, 11));
+        assert_eq!(index[1].offset, 63);
+
+        let error = read_fai("test_data/malformed.fa.fai").unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::InvalidIndex(_)));
+        assert_eq!(error.line, Some(2));
+    }
+
+    #[test]
+    fn test_fetch() {
+        let fasta = Fasta::open("test_data/test.fa").unwrap();
+        assert_eq!(fasta.length("chr2"), Some(12));
+
+        assert_eq!(fasta.fetch("chr1", 0, 4).unwrap(), b"ACGT");
+        // Across line breaks
+        assert_eq!(fasta.fetch("chr1", 8, 22).unwrap(), b"TTGGGGGGGGGGCC");
+        // Clamped to the end of the sequence
+        assert_eq!(fasta.fetch("chr1", 30, 100).unwrap(), b"AAAAA");
+        assert_eq!(fasta.fetch("chr2", 10, 12).unwrap(), b"nn");
+        assert!(fasta.fetch("chr2", 12, 20).unwrap().is_empty());
+
+        let err = fasta.fetch("chr3", 0, 10).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::UnknownSequence("chr3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_fetch_bgzf() {
+        let plain = Fasta::open("test_data/test.fa").unwrap();
+        let fasta = Fasta::open("test_data/test.fa.gz").unwrap();
+        assert_eq!(fasta.compression, Compression::Bgzf);
+        assert!(fasta.gzi.is_some());
+
+        for (landmark, start, end) in [("chr1", 0, 35), ("chr1", 8, 22), ("chr2", 3, 12)] {
+            assert_eq!(
+                fasta.fetch(landmark, start, end).unwrap(),
+                plain.fetch(landmark, start, end).unwrap()
+            );
+        }
+
+        assert_eq!(build_fai("test_data/test.fa.gz").unwrap(), plain.index);
+    }
+}
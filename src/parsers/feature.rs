@@ -1,29 +1,131 @@
 use bevy::prelude::*;
 use std::str::FromStr;
 
+use super::attributes::*;
+use super::error::*;
+use crate::structs::Orientation;
+
 #[derive(PartialEq, Clone, Debug, Default, Component)]
 pub struct Feature {
     pub name: String,
     pub landmark: String,
+    pub source: String,
     pub start: usize,
     pub end: usize,
     pub feature_type: String,
+    pub score: Option<f64>,
+    pub strand: Option<Orientation>, // None for unstranded (.) or unknown (?)
+    pub phase: Option<u8>,
+    pub attributes: Attributes,
     pub subfeatures: Option<Vec<Feature>>,
     pub y_offset: usize,
+    pub color: Option<[u8; 3]>, // From BED itemRgb, otherwise colored by type
 }
 
 impl Feature {
-    pub fn from_gff3_line(line: &str) -> Result<Feature, String> {
+    pub fn children(&self) -> &[Feature] {
+        self.subfeatures.as_deref().unwrap_or(&[])
+    }
+
+    pub fn from_gff3_line(line: &str) -> Result<Feature, ParseError> {
         let split = line.splitn(9, '\t').collect::<Vec<&str>>();
 
+        if split.len() < 8 {
+            return Err(ParseError::new(ParseErrorKind::MissingField)
+                .with_field(split.len() + 1, GFF3_COLUMNS[split.len()]));
+        }
+
+        let invalid = |kind: ParseErrorKind, n: usize| {
+            ParseError::new(kind).with_field(n + 1, GFF3_COLUMNS[n])
+        };
+
+        let coordinate = |n: usize| {
+            usize::from_str(split[n]).map_err(|_| invalid(ParseErrorKind::InvalidInteger, n))
+        };
+
+        let score = match split[5] {
+            "." => None,
+            x => Some(f64::from_str(x).map_err(|_| invalid(ParseErrorKind::InvalidFloat, 5))?),
+        };
+
+        let strand = match split[6] {
+            "." | "?" => None,
+            x => Some(
+                x.parse::<Orientation>()
+                    .map_err(|_| invalid(ParseErrorKind::InvalidOrientation, 6))?,
+            ),
+        };
+
+        let phase = match split[7] {
+            "." => None,
+            "0" => Some(0),
+            "1" => Some(1),
+            "2" => Some(2),
+            _ => return Err(invalid(ParseErrorKind::InvalidValue, 7)),
+        };
+
+        let attributes = match split.get(8) {
+            Some(x) => Attributes::from_gff3(x)?,
+            None => Attributes::default(),
+        };
+
+        // Labels prefer Name, then fall back to ID
+        let name = attributes
+            .name
+            .clone()
+            .or_else(|| attributes.id.clone())
+            .unwrap_or_default();
+
         Ok(Feature {
-            name: String::from("Hello"),
+            name,
             landmark: split[0].to_string(),
-            start: usize::from_str(split[3]).unwrap(),
-            end: usize::from_str(split[4]).unwrap(),
+            source: split[1].to_string(),
+            start: coordinate(3)?,
+            end: coordinate(4)?,
             feature_type: split[2].to_string(),
+            score,
+            strand,
+            phase,
+            attributes,
             subfeatures: None,
             y_offset: 0,
+            color: None,
         })
     }
 }
+
+const GFF3_COLUMNS: [&str; 9] = [
+    "seqid",
+    "source",
+    "type",
+    "start",
+    "end",
+    "score",
+    "strand",
+    "phase",
+    "attributes",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_gff3_line() {
+        let feature = Feature::from_gff3_line(
+            "ctg123\tgenbank\tCDS\t1201\t1500\t0.5\t-\t2\tID=cds1;Parent=mRNA1;Name=edenprotein.1",
+        )
+        .unwrap();
+
+        assert_eq!(feature.name, "edenprotein.1");
+        assert_eq!(feature.source, "genbank");
+        assert_eq!(feature.score, Some(0.5));
+        assert_eq!(feature.strand, Some(Orientation::Negative));
+        assert_eq!(feature.phase, Some(2));
+        assert_eq!(feature.attributes.parent, vec!["mRNA1"]);
+
+        let err = Feature::from_gff3_line("ctg123\t.\tgene\t1\t10\t.\tx\t.\t.").unwrap_err();
+        assert_eq!(err.column, Some(7));
+        assert_eq!(err.field.as_deref(), Some("strand"));
+    }
+}
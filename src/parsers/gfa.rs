@@ -1,4 +1,3 @@
-use bytelines::*;
 use memchr::memchr_iter;
 use simdutf8::basic::from_utf8;
 use std::str::FromStr;
@@ -6,12 +5,17 @@ use twox_hash::RandomXxh3HashBuilder64;
 
 use crate::structs::*;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::io::{BufRead, Seek, SeekFrom, Write};
 use std::num::NonZeroUsize;
 use std::sync::Arc;
 
+use super::bgzf::*;
+use super::cigar::*;
+use super::error::*;
+use super::fasta::write_fasta_record;
 use super::feature::*;
+use super::progress::*;
+use super::sequence::*;
 
 #[derive(Clone, Debug)]
 pub struct Gfa {
@@ -20,115 +24,969 @@ pub struct Gfa {
     pub lengths: HashMap<String, usize, RandomXxh3HashBuilder64>,
     pub links: Vec<Arc<Link>>,
     pub links_atlas: HashMap<String, Vec<Arc<Link>>, RandomXxh3HashBuilder64>,
+    pub header: Header,
+    pub paths: HashMap<String, Path, RandomXxh3HashBuilder64>,
+    pub paths_atlas: HashMap<String, Vec<String>, RandomXxh3HashBuilder64>, // Segment ID -> path names
+    pub walks: HashMap<String, Walk, RandomXxh3HashBuilder64>,
+    pub walks_atlas: HashMap<String, Vec<String>, RandomXxh3HashBuilder64>, // Segment ID -> walk names
+    pub containments: Vec<Arc<Containment>>,
+    pub containments_atlas: HashMap<String, Vec<Arc<Containment>>, RandomXxh3HashBuilder64>,
+    pub version: GfaVersion,
+    pub fragments: HashMap<String, Vec<Fragment>, RandomXxh3HashBuilder64>, // Segment ID -> fragments
+    pub gaps: Vec<Arc<Gap>>,
+    pub gaps_atlas: HashMap<String, Vec<Arc<Gap>>, RandomXxh3HashBuilder64>,
+    pub groups: HashMap<String, Group, RandomXxh3HashBuilder64>,
+    pub sequences: SequenceStore,
+    pub warnings: Warnings,
 }
 
 impl Gfa {
-    pub fn parse<T>(filename: T) -> Result<Gfa, String>
+    /// Parses GFA1 and GFA2. The dialect is taken from the VN:Z: header tag,
+    /// files without one are read as GFA1.
+    pub fn parse<T>(filename: T) -> Result<Gfa, ParseError>
+    where
+        T: ToString,
+    {
+        Gfa::parse_with(filename, &ParseOptions::default())
+    }
+
+    pub fn parse_with<T>(filename: T, options: &ParseOptions) -> Result<Gfa, ParseError>
     where
         T: ToString,
     {
         let filename = filename.to_string();
 
-        let file = match File::open(&filename) {
-            Ok(x) => BufReader::new(x),
-            Err(_) => return Err(format!("Unable to open file {}", &filename)),
+        let mut file = match InputFile::open(&filename) {
+            Ok(x) => x,
+            Err(err) => return Err(ParseError::from(err).with_file(&filename)),
+        };
+
+        let mut gfa = Gfa {
+            filename: filename.clone(),
+            segments: HashMap::with_capacity_and_hasher(
+                1024 * 10,
+                RandomXxh3HashBuilder64::default(),
+            ), //Default::default();
+            lengths: HashMap::with_capacity_and_hasher(
+                1024 * 10,
+                RandomXxh3HashBuilder64::default(),
+            ), // Default::default();
+            links: Vec::with_capacity(5 * 1024 * 1024),
+            links_atlas: HashMap::with_capacity_and_hasher(
+                1024 * 10,
+                RandomXxh3HashBuilder64::default(),
+            ),
+            header: Header::default(),
+            paths: Default::default(),
+            paths_atlas: Default::default(),
+            walks: Default::default(),
+            walks_atlas: Default::default(),
+            containments: Vec::new(),
+            containments_atlas: Default::default(),
+            version: GfaVersion::V1,
+            fragments: Default::default(),
+            gaps: Vec::new(),
+            gaps_atlas: Default::default(),
+            groups: Default::default(),
+            sequences: SequenceStore::new(options.sequences, &filename),
+            warnings: Warnings::default(),
         };
 
-        let mut segments: HashMap<String, Segment, RandomXxh3HashBuilder64> =
-            HashMap::with_capacity_and_hasher(1024 * 10, RandomXxh3HashBuilder64::default()); //Default::default();
-        let mut lengths: HashMap<String, usize, RandomXxh3HashBuilder64> =
-            HashMap::with_capacity_and_hasher(1024 * 10, RandomXxh3HashBuilder64::default()); // Default::default();
-        let mut links: Vec<Arc<Link>> = Vec::with_capacity(5 * 1024 * 1024);
-        let mut links_atlas: HashMap<String, Vec<Arc<Link>>, RandomXxh3HashBuilder64> =
-            HashMap::with_capacity_and_hasher(1024 * 10, RandomXxh3HashBuilder64::default());
-
-        let mut lines = file.byte_lines();
-
-        while let Some(line) = lines.next() {
-            let line = line.unwrap();
-            //let split = line[..].split(|&x| x == '\t' as u8).collect::<Vec<&[u8]>>();
-
-            if line[0] == 'S' as u8 {
-                let split = memchr_iter('\t' as u8, &line[..]).collect::<Vec<usize>>();
-                // Segment line
-                let mut segment = Segment::default();
-                let id = from_utf8(&line[split[0] + 1..split[1]])
-                    .unwrap()
-                    .to_string();
-                let length = split[2] - split[1] - 1;
-                segment.id = id.clone();
-                lengths.insert(id.clone(), length);
-
-                //for tag in split[3..].iter() {
-                for tag_loc in 2..split.len() {
-                    let tag = from_utf8(if tag_loc + 1 >= split.len() {
-                        &line[split[tag_loc] + 1..]
+        let mut line: Vec<u8> = Vec::with_capacity(8192);
+        let mut line_number: usize = 0;
+
+        if let Some(progress) = &options.progress {
+            progress.start(&filename);
+        }
+
+        loop {
+            line.clear();
+            line_number += 1;
+
+            if line_number % PROGRESS_LINES == 0 {
+                if let Some(progress) = &options.progress {
+                    progress.update(&file);
+                }
+            }
+
+            // Kept so on-disk sequences can be found again
+            let position = file.position();
+
+            match file.read_until(b'\n', &mut line) {
+                Ok(0) => break, // EOF
+                Ok(_) => (),
+                Err(err) => {
+                    return Err(ParseError::from(err)
+                        .at_line(line_number)
+                        .with_file(&gfa.filename))
+                }
+            };
+
+            while matches!(line.last(), Some(b'\n') | Some(b'\r')) {
+                line.pop();
+            }
+
+            // Blank lines and comments
+            if line.is_empty() || line[0] == b'#' {
+                continue;
+            }
+
+            if let Err(err) = gfa.parse_record(&line, line_number, position) {
+                let err = err.at_line(line_number).with_file(&gfa.filename);
+                options.mode.recover(err, &mut gfa.warnings)?;
+            }
+        }
+
+        if let Some(progress) = &options.progress {
+            progress.finish();
+        }
+
+        // Ordered groups are GFA2's paths, so expose them as such (edge references are dropped)
+        for group in gfa.groups.values().filter(|x| x.ordered) {
+            let path = Path {
+                name: group.id.clone(),
+                segments: group
+                    .members
+                    .iter()
+                    .filter(|(id, _)| gfa.segments.contains_key(id))
+                    .map(|(id, orient)| (id.clone(), orient.unwrap_or_default()))
+                    .collect(),
+                overlaps: None,
+            };
+
+            for (segment, _) in path.segments.iter() {
+                let visits = gfa.paths_atlas.entry(segment.clone()).or_default();
+                if !visits.contains(&path.name) {
+                    visits.push(path.name.clone());
+                }
+            }
+
+            gfa.paths.insert(path.name.clone(), path);
+        }
+
+        Ok(gfa)
+    }
+
+    fn parse_record(
+        &mut self,
+        line: &[u8],
+        line_number: usize,
+        file_position: u64,
+    ) -> Result<(), ParseError> {
+        // Segment lines can carry Mbs of sequence, so they avoid splitting the whole line
+        if line[0] == b'S' {
+            return self.parse_segment(line, file_position);
+        }
+
+        // Everything else is short enough to just split
+        let fields = from_utf8(line)
+            .map_err(|_| ParseError::new(ParseErrorKind::InvalidUtf8))?
+            .split('\t')
+            .collect::<Vec<&str>>();
+
+        match line[0] {
+            b'H' => {
+                for tag in fields[1..].iter().filter(|x| !x.is_empty()) {
+                    if let Some(vn) = tag.strip_prefix("VN:Z:") {
+                        if vn.starts_with('2') {
+                            self.version = GfaVersion::V2;
+                        }
+                        self.header.version = Some(vn.to_string());
                     } else {
-                        &line[split[tag_loc] + 1..split[tag_loc + 1]]
-                    })
-                    .unwrap();
-                    if tag.starts_with("LN:i:") {
-                        segment.length = Some(tag[5..].parse::<NonZeroUsize>().unwrap());
-                        assert!(segment.length.unwrap().get() == length);
-                    } else if tag.starts_with("RC:i:") {
-                        segment.read_count = Some(tag[5..].parse::<NonZeroUsize>().unwrap());
-                    } else if tag.starts_with("FC:i:") {
-                        segment.fragment_count = Some(tag[5..].parse::<NonZeroUsize>().unwrap());
-                    } else if tag.starts_with("KC:i:") {
-                        segment.kmer_count = Some(tag[5..].parse::<NonZeroUsize>().unwrap());
-                    } else if tag.starts_with("CS:Z:") {
-                        segment.checksum = tag[5..].parse::<u64>().unwrap();
-                    } else if tag.starts_with("UR:Z:") {
-                        segment.path = Some(tag[5..].to_string());
+                        self.header.tags.push(tag.to_string());
+                    }
+                }
+            }
+            b'L' => {
+                let mut link = Link {
+                    from: field(&fields, 1, "from")?.to_string(),
+                    from_orient: orientation(&fields, 2, "from_orient")?,
+                    to: field(&fields, 3, "to")?.to_string(),
+                    to_orient: orientation(&fields, 4, "to_orient")?,
+                    overlap: overlap(&fields, 5, "overlap")?,
+                    ..Default::default()
+                };
+
+                parse_link_tags(&mut link, &fields, 6)?;
+
+                insert_link(link, &mut self.links, &mut self.links_atlas);
+            }
+            b'P' => {
+                let path = Path {
+                    name: field(&fields, 1, "name")?.to_string(),
+                    segments: parse_path_segments(field(&fields, 2, "segments")?)
+                        .ok_or_else(|| invalid(ParseErrorKind::InvalidValue, 2, "segments"))?,
+                    overlaps: match field(&fields, 3, "overlaps")? {
+                        "*" => None,
+                        x => Some(x.split(',').map(|x| x.to_string()).collect()),
+                    },
+                };
+
+                // Replacing it would leave the first path's segments pointing at the second
+                if self.paths.contains_key(&path.name) {
+                    return Err(invalid(ParseErrorKind::InvalidValue, 2, "name"));
+                }
+
+                for (segment, _) in path.segments.iter() {
+                    let visits = self.paths_atlas.entry(segment.clone()).or_default();
+                    if !visits.contains(&path.name) {
+                        visits.push(path.name.clone());
+                    }
+                }
+
+                self.paths.insert(path.name.clone(), path);
+            }
+            b'W' => {
+                let walk = Walk {
+                    sample: field(&fields, 1, "sample")?.to_string(),
+                    haplotype: integer(&fields, 2, "haplotype")?,
+                    sequence: field(&fields, 3, "sequence")?.to_string(),
+                    start: field(&fields, 4, "start")?.parse::<usize>().ok(),
+                    end: field(&fields, 5, "end")?.parse::<usize>().ok(),
+                    segments: parse_walk_segments(field(&fields, 6, "walk")?)
+                        .ok_or_else(|| invalid(ParseErrorKind::InvalidValue, 6, "walk"))?,
+                };
+
+                let name = walk.name();
+
+                for (segment, _) in walk.segments.iter() {
+                    let visits = self.walks_atlas.entry(segment.clone()).or_default();
+                    if !visits.contains(&name) {
+                        visits.push(name.clone());
                     }
                 }
-                segments.insert(id.clone(), segment);
-                // println!("{} {}", id, length);
-            } else if line[0] == 'L' as u8 {
-                // Won't have Mbs of data so we can just parse the entire line here without a slowdown...
-                let linkline = from_utf8(&line[2..])
-                    .unwrap()
-                    .split('\t')
-                    .collect::<Vec<&str>>();
-                // Link line
-                let link = Link {
-                    from: linkline[0].to_string(),
-                    from_orient: linkline[1].parse::<Orientation>().unwrap(),
-                    to: linkline[2].to_string(),
-                    to_orient: linkline[3].parse::<Orientation>().unwrap(),
-                    overlap: None,
-                    //from: from_utf8(&line[split[1]+1..split[2]]).unwrap().to_string(),
-                    //from_orient: from_utf8(&line[split[2]+1..split[3]]).unwrap().parse::<Orientation>().unwrap(),
-                    //to: from_utf8(&line[split[3]+1..split[4]]).unwrap().to_string(),
-                    //to_orient: from_utf8(&line[split[4]+1..split[5]]).unwrap().parse::<Orientation>().unwrap(),
-                    //overlap: None,
+
+                self.walks.insert(name, walk);
+            }
+            b'C' => {
+                let containment = Containment {
+                    container: field(&fields, 1, "container")?.to_string(),
+                    container_orient: orientation(&fields, 2, "container_orient")?,
+                    contained: field(&fields, 3, "contained")?.to_string(),
+                    contained_orient: orientation(&fields, 4, "contained_orient")?,
+                    pos: integer(&fields, 5, "pos")?,
+                    overlap: overlap(&fields, 6, "overlap")?,
+                };
+
+                insert_containment(
+                    containment,
+                    &mut self.containments,
+                    &mut self.containments_atlas,
+                );
+            }
+            b'E' => {
+                let from = reference(&fields, 2, "sid1")?;
+                let to = reference(&fields, 3, "sid2")?;
+                let mut positions = [Position::default(); 4];
+                for (n, name) in ["beg1", "end1", "beg2", "end2"].iter().enumerate() {
+                    positions[n] = position(&fields, 4 + n, name)?;
+                }
+                let alignment = parse_alignment(field(&fields, 8, "alignment")?);
+
+                match edge_to_gfa1(from, to, positions, alignment) {
+                    Some(Gfa1Edge::Link(mut link)) => {
+                        link.id = match field(&fields, 1, "eid")? {
+                            "*" => None,
+                            x => Some(x.to_string()),
+                        };
+                        parse_link_tags(&mut link, &fields, 9)?;
+                        insert_link(link, &mut self.links, &mut self.links_atlas)
+                    }
+                    Some(Gfa1Edge::Containment(containment)) => insert_containment(
+                        containment,
+                        &mut self.containments,
+                        &mut self.containments_atlas,
+                    ),
+                    // Internal alignments have no GFA1 counterpart and are not drawn
+                    None => (),
+                }
+            }
+            b'F' => {
+                let (external, external_orient) = reference(&fields, 2, "external")?;
+
+                let fragment = Fragment {
+                    segment: field(&fields, 1, "sid")?.to_string(),
+                    external,
+                    external_orient,
+                    segment_range: (position(&fields, 3, "sbeg")?, position(&fields, 4, "send")?),
+                    fragment_range: (position(&fields, 5, "fbeg")?, position(&fields, 6, "fend")?),
+                    alignment: parse_alignment(field(&fields, 7, "alignment")?),
                 };
 
-                let link = Arc::new(link);
+                self.fragments
+                    .entry(fragment.segment.clone())
+                    .or_default()
+                    .push(fragment);
+            }
+            b'G' => {
+                let (from, from_orient) = reference(&fields, 2, "sid1")?;
+                let (to, to_orient) = reference(&fields, 3, "sid2")?;
+
+                let gap = Arc::new(Gap {
+                    id: match field(&fields, 1, "gid")? {
+                        "*" => None,
+                        x => Some(x.to_string()),
+                    },
+                    from,
+                    from_orient,
+                    to,
+                    to_orient,
+                    distance: field(&fields, 4, "dist")?
+                        .parse::<i64>()
+                        .map_err(|_| invalid(ParseErrorKind::InvalidInteger, 4, "dist"))?,
+                    variance: field(&fields, 5, "var")?.parse::<usize>().ok(),
+                });
 
-                links.push(Arc::clone(&link));
-                links_atlas
-                    .entry(link.from.clone())
+                self.gaps.push(Arc::clone(&gap));
+                self.gaps_atlas
+                    .entry(gap.from.clone())
                     .or_default()
-                    .push(Arc::clone(&link));
-                links_atlas
-                    .entry(link.to.clone())
+                    .push(Arc::clone(&gap));
+                self.gaps_atlas
+                    .entry(gap.to.clone())
                     .or_default()
-                    .push(Arc::clone(&link));
+                    .push(Arc::clone(&gap));
+            }
+            b'O' | b'U' => {
+                let ordered = line[0] == b'O';
+                let refs = field(&fields, 2, "refs")?;
+
+                let members = if ordered {
+                    refs.split(' ')
+                        .filter(|x| !x.is_empty())
+                        .map(|x| parse_reference(x).map(|(id, orient)| (id, Some(orient))))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| invalid(ParseErrorKind::InvalidOrientation, 2, "refs"))?
+                } else {
+                    refs.split(' ')
+                        .filter(|x| !x.is_empty())
+                        .map(|x| (x.to_string(), None))
+                        .collect()
+                };
+
+                let id = match field(&fields, 1, "id")? {
+                    "*" => format!("group_{}", line_number),
+                    x => x.to_string(),
+                };
+
+                self.groups.insert(
+                    id.clone(),
+                    Group {
+                        id,
+                        ordered,
+                        members,
+                    },
+                );
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn parse_segment(&mut self, line: &[u8], file_position: u64) -> Result<(), ParseError> {
+        let split = memchr_iter(b'\t', line).collect::<Vec<usize>>();
+        let field_end = |n: usize| split.get(n).copied().unwrap_or(line.len());
+
+        if split.len() < 2 {
+            return Err(invalid(ParseErrorKind::MissingField, 3, "sequence"));
+        }
+
+        let mut segment = Segment::default();
+        let id = from_utf8(&line[split[0] + 1..split[1]])
+            .map_err(|_| invalid(ParseErrorKind::InvalidUtf8, 2, "id"))?
+            .to_string();
+
+        // GFA2 has an explicit length field ahead of the sequence
+        let (mut length, tags_from) = match self.version {
+            GfaVersion::V1 => {
+                let sequence = &line[split[1] + 1..field_end(2)];
+                // * means the sequence isn't stored, the length then comes from LN:i:
+                (if sequence == b"*" { 0 } else { sequence.len() }, 2)
+            }
+            GfaVersion::V2 => {
+                let length = from_utf8(&line[split[1] + 1..field_end(2)])
+                    .ok()
+                    .and_then(|x| x.parse::<usize>().ok())
+                    .ok_or_else(|| invalid(ParseErrorKind::InvalidInteger, 3, "slen"))?;
+                segment.length = NonZeroUsize::new(length);
+                (length, 3)
+            }
+        };
+
+        // Column of the sequence itself, if it's there
+        let sequence = split
+            .get(tags_from - 1)
+            .map(|&x| (x + 1, field_end(tags_from)))
+            .filter(|&(start, end)| &line[start..end] != b"*");
+        segment.id = id.clone();
+
+        //for tag in split[3..].iter() {
+        for tag_loc in tags_from..split.len() {
+            let column = tag_loc + 2;
+            let tag = from_utf8(&line[split[tag_loc] + 1..field_end(tag_loc + 1)])
+                .map_err(|_| invalid(ParseErrorKind::InvalidUtf8, column, "tag"))?;
+
+            let count = |name: &str| {
+                tag[5..]
+                    .parse::<NonZeroUsize>()
+                    .map_err(|_| invalid(ParseErrorKind::InvalidInteger, column, name))
+            };
+
+            if tag.starts_with("LN:i:") {
+                let ln = count("LN:i")?;
+                if length == 0 {
+                    length = ln.get();
+                } else if ln.get() != length {
+                    return Err(invalid(
+                        ParseErrorKind::LengthMismatch {
+                            expected: ln.get(),
+                            found: length,
+                        },
+                        column,
+                        "LN:i",
+                    ));
+                }
+                segment.length = Some(ln);
+            } else if tag.starts_with("RC:i:") {
+                segment.read_count = Some(count("RC:i")?);
+            } else if tag.starts_with("FC:i:") {
+                segment.fragment_count = Some(count("FC:i")?);
+            } else if tag.starts_with("KC:i:") {
+                segment.kmer_count = Some(count("KC:i")?);
+            } else if tag.starts_with("CS:Z:") {
+                // Only numeric checksums fit, anything else is ignored rather than rejected
+                if let Ok(checksum) = tag[5..].parse::<u64>() {
+                    segment.checksum = checksum;
+                }
+            } else if tag.starts_with("UR:Z:") {
+                segment.path = Some(tag[5..].to_string());
+            }
+        }
+
+        segment.length = segment.length.or_else(|| NonZeroUsize::new(length));
+
+        if let Some((start, end)) = sequence {
+            match self.sequences.storage {
+                SequenceStorage::Packed => self.sequences.insert(&id, &line[start..end]),
+                SequenceStorage::OnDisk => {
+                    self.sequences
+                        .insert_on_disk(&id, file_position, start, end - start)
+                }
+                SequenceStorage::Discard => (),
+            }
+        }
+
+        self.lengths.insert(id.clone(), length);
+        self.segments.insert(id, segment);
+        // println!("{} {}", id, length);
+
+        Ok(())
+    }
+
+    /// Names of all paths (P lines) passing through a segment
+    pub fn paths_through(&self, segment: &str) -> &[String] {
+        self.paths_atlas
+            .get(segment)
+            .map(|x| x.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Names of all walks (W lines) passing through a segment
+    pub fn walks_through(&self, segment: &str) -> &[String] {
+        self.walks_atlas
+            .get(segment)
+            .map(|x| x.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// A segment's sequence as read in the given orientation
+    pub fn segment_sequence(
+        &self,
+        segment: &str,
+        orientation: Orientation,
+    ) -> Result<Vec<u8>, ParseError> {
+        let sequence = self.sequences.get(segment)?;
+        match orientation {
+            Orientation::Positive => Ok(sequence),
+            Orientation::Negative => Ok(reverse_complement(&sequence)),
+        }
+    }
+
+    /// Spelled out sequence of a path or walk (by its PanSN name). Overlaps from the P line are
+    /// only taken off the next segment when they're given as CIGARs.
+    pub fn path_sequence(&self, name: &str) -> Result<Vec<u8>, ParseError> {
+        let (segments, overlaps) = match (self.paths.get(name), self.walks.get(name)) {
+            (Some(path), _) => (&path.segments, path.overlaps.as_ref()),
+            (None, Some(walk)) => (&walk.segments, None),
+            (None, None) => {
+                return Err(ParseError::new(ParseErrorKind::UnknownSequence(
+                    name.to_string(),
+                )))
+            }
+        };
+
+        let mut sequence = Vec::new();
+
+        for (n, (segment, orientation)) in segments.iter().enumerate() {
+            let bases = self.segment_sequence(segment, *orientation)?;
+
+            let overlap = match (n, overlaps) {
+                (0, _) | (_, None) => 0,
+                (n, Some(overlaps)) => overlaps
+                    .get(n - 1)
+                    .and_then(|x| x.parse::<Cigar>().ok())
+                    .map(|x| x.query_length())
+                    .unwrap_or_default(),
+            };
+
+            sequence.extend_from_slice(&bases[std::cmp::min(overlap, bases.len())..]);
+        }
+
+        Ok(sequence)
+    }
+
+    /// Writes paths or walks (by name) as FASTA records
+    pub fn write_fasta<W: Write>(&self, out: &mut W, names: &[&str]) -> Result<(), ParseError> {
+        for name in names {
+            let sequence = self.path_sequence(name)?;
+            write_fasta_record(out, name, &sequence, 60)?;
+        }
+        Ok(())
+    }
+}
+
+fn insert_link(
+    link: Link,
+    links: &mut Vec<Arc<Link>>,
+    links_atlas: &mut HashMap<String, Vec<Arc<Link>>, RandomXxh3HashBuilder64>,
+) {
+    let link = Arc::new(link);
+
+    links.push(Arc::clone(&link));
+    links_atlas
+        .entry(link.from.clone())
+        .or_default()
+        .push(Arc::clone(&link));
+    links_atlas
+        .entry(link.to.clone())
+        .or_default()
+        .push(Arc::clone(&link));
+}
+
+fn insert_containment(
+    containment: Containment,
+    containments: &mut Vec<Arc<Containment>>,
+    containments_atlas: &mut HashMap<String, Vec<Arc<Containment>>, RandomXxh3HashBuilder64>,
+) {
+    let containment = Arc::new(containment);
+
+    containments.push(Arc::clone(&containment));
+    containments_atlas
+        .entry(containment.container.clone())
+        .or_default()
+        .push(Arc::clone(&containment));
+    containments_atlas
+        .entry(containment.contained.clone())
+        .or_default()
+        .push(Arc::clone(&containment));
+}
+
+enum Gfa1Edge {
+    Link(Link),
+    Containment(Containment),
+}
+
+// Maps a GFA2 edge onto GFA1 semantics. Positions on both segments are given on the forward
+// strand, so whether an overlap sits at the start or end depends on the orientation.
+fn edge_to_gfa1(
+    (from, from_orient): (String, Orientation),
+    (to, to_orient): (String, Orientation),
+    [beg1, end1, beg2, end2]: [Position; 4],
+    overlap: Option<Cigar>,
+) -> Option<Gfa1Edge> {
+    let whole_from = beg1.pos == 0 && end1.is_end;
+    let whole_to = beg2.pos == 0 && end2.is_end;
+
+    if whole_to {
+        return Some(Gfa1Edge::Containment(Containment {
+            container: from,
+            container_orient: from_orient,
+            contained: to,
+            contained_orient: to_orient,
+            pos: beg1.pos,
+            overlap,
+        }));
+    } else if whole_from {
+        return Some(Gfa1Edge::Containment(Containment {
+            container: to,
+            container_orient: to_orient,
+            contained: from,
+            contained_orient: from_orient,
+            pos: beg2.pos,
+            overlap,
+        }));
+    }
+
+    let dovetail = (beg1.pos == 0 || end1.is_end) && (beg2.pos == 0 || end2.is_end);
+    if !dovetail {
+        return None;
+    }
+
+    let from_at_end = match from_orient {
+        Orientation::Positive => end1.is_end,
+        Orientation::Negative => beg1.pos == 0,
+    };
+    let to_at_start = match to_orient {
+        Orientation::Positive => beg2.pos == 0,
+        Orientation::Negative => end2.is_end,
+    };
+
+    let link = match (from_at_end, to_at_start) {
+        (true, true) => Link {
+            from,
+            from_orient,
+            to,
+            to_orient,
+            overlap,
+            ..Default::default()
+        },
+        (false, false) => Link {
+            from: to,
+            from_orient: to_orient,
+            to: from,
+            to_orient: from_orient,
+            overlap,
+            ..Default::default()
+        },
+        (true, false) => Link {
+            from,
+            from_orient,
+            to,
+            to_orient: to_orient.flip(),
+            overlap,
+            ..Default::default()
+        },
+        (false, true) => Link {
+            from,
+            from_orient: from_orient.flip(),
+            to,
+            to_orient,
+            overlap,
+            ..Default::default()
+        },
+    };
+
+    Some(Gfa1Edge::Link(link))
+}
+
+fn invalid(kind: ParseErrorKind, column: usize, name: &str) -> ParseError {
+    ParseError::new(kind).with_field(column, name)
+}
+
+// Fields are 0-indexed with the record type at 0, columns are reported 1-based
+fn field<'a>(fields: &[&'a str], n: usize, name: &str) -> Result<&'a str, ParseError> {
+    fields
+        .get(n)
+        .copied()
+        .ok_or_else(|| invalid(ParseErrorKind::MissingField, n + 1, name))
+}
+
+fn integer(fields: &[&str], n: usize, name: &str) -> Result<usize, ParseError> {
+    field(fields, n, name)?
+        .parse::<usize>()
+        .map_err(|_| invalid(ParseErrorKind::InvalidInteger, n + 1, name))
+}
+
+fn orientation(fields: &[&str], n: usize, name: &str) -> Result<Orientation, ParseError> {
+    field(fields, n, name)?
+        .parse::<Orientation>()
+        .map_err(|_| invalid(ParseErrorKind::InvalidOrientation, n + 1, name))
+}
+
+fn reference(fields: &[&str], n: usize, name: &str) -> Result<(String, Orientation), ParseError> {
+    parse_reference(field(fields, n, name)?)
+        .ok_or_else(|| invalid(ParseErrorKind::InvalidOrientation, n + 1, name))
+}
+
+fn position(fields: &[&str], n: usize, name: &str) -> Result<Position, ParseError> {
+    field(fields, n, name)?
+        .parse::<Position>()
+        .map_err(|_| invalid(ParseErrorKind::InvalidInteger, n + 1, name))
+}
+
+// GFA2 references: 11+
+fn parse_reference(field: &str) -> Option<(String, Orientation)> {
+    if field.len() < 2 || !field.is_char_boundary(field.len() - 1) {
+        return None;
+    }
+    let (id, orient) = field.split_at(field.len() - 1);
+    Some((id.to_string(), orient.parse::<Orientation>().ok()?))
+}
+
+// GFA1 overlaps are a CIGAR or *
+fn overlap(fields: &[&str], n: usize, name: &str) -> Result<Option<Cigar>, ParseError> {
+    match field(fields, n, name)? {
+        "*" => Ok(None),
+        x => x
+            .parse::<Cigar>()
+            .map(Some)
+            .map_err(|_| invalid(ParseErrorKind::InvalidValue, n + 1, name)),
+    }
+}
+
+// GFA2 alignments are either a CIGAR string, a trace (comma-separated integers) or *. Only
+// CIGARs are kept.
+fn parse_alignment(field: &str) -> Option<Cigar> {
+    field.parse::<Cigar>().ok()
+}
+
+// Optional tags from field n on, unknown ones are skipped
+fn parse_link_tags(link: &mut Link, fields: &[&str], n: usize) -> Result<(), ParseError> {
+    for (column, tag) in fields.iter().enumerate().skip(n) {
+        let column = column + 1;
+        let (key, value) = match (tag.get(..5), tag.get(5..)) {
+            (Some(key), Some(value)) => (key, value),
+            _ => continue,
+        };
+
+        let count = |name: &str| {
+            value
+                .parse::<usize>()
+                .map_err(|_| invalid(ParseErrorKind::InvalidInteger, column, name))
+        };
+
+        match key {
+            "MQ:i:" => {
+                link.mapping_quality = Some(
+                    value
+                        .parse::<u8>()
+                        .map_err(|_| invalid(ParseErrorKind::InvalidInteger, column, "MQ:i"))?,
+                )
             }
+            "NM:i:" => link.mismatches = Some(count("NM:i")?),
+            "RC:i:" => link.read_count = NonZeroUsize::new(count("RC:i")?),
+            "FC:i:" => link.fragment_count = NonZeroUsize::new(count("FC:i")?),
+            "KC:i:" => link.kmer_count = NonZeroUsize::new(count("KC:i")?),
+            "ID:Z:" => link.id = Some(value.to_string()),
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+// P line segment names: 11+,12-,13+
+fn parse_path_segments(field: &str) -> Option<Vec<(String, Orientation)>> {
+    field.split(',').map(parse_reference).collect()
+}
+
+// W line walks: >11<12>13
+fn parse_walk_segments(field: &str) -> Option<Vec<(String, Orientation)>> {
+    let mut segments = Vec::new();
+
+    if field == "*" {
+        return Some(segments);
+    }
+
+    let mut rest = field;
+    while !rest.is_empty() {
+        let orient = match rest.as_bytes()[0] {
+            b'>' => Orientation::Positive,
+            b'<' => Orientation::Negative,
+            _ => return None,
+        };
+        rest = &rest[1..];
+
+        let end = rest.find(|c| c == '>' || c == '<').unwrap_or(rest.len());
+        if end == 0 {
+            return None;
         }
 
-        Ok(Gfa {
-            filename,
-            segments,
-            lengths,
-            links,
-            links_atlas,
-        })
+        segments.push((rest[..end].to_string(), orient));
+        rest = &rest[end..];
     }
+
+    Some(segments)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn test_parse_paths_walks_containments() {
+        let gfa = Gfa::parse("test_data/test.gfa").expect("Unable to parse GFA");
+
+        assert_eq!(gfa.header.version.as_deref(), Some("1.1"));
+        assert_eq!(gfa.segments.len(), 4);
+        assert_eq!(gfa.links.len(), 3);
+
+        let path = gfa.paths.get("ref").unwrap();
+        assert_eq!(path.segments.len(), 3);
+        assert_eq!(path.segments[1].0, "s2");
+        assert!(matches!(path.segments[1].1, Orientation::Negative));
+        assert_eq!(path.overlaps.as_ref().unwrap().len(), 2);
+
+        let walk = gfa.walks.get("HG002#1#chr1:0-12").unwrap();
+        assert_eq!(walk.segments.len(), 3);
+        assert!(matches!(walk.segments[1].1, Orientation::Negative));
+
+        assert_eq!(gfa.paths_through("s3"), &["ref".to_string()]);
+        assert_eq!(gfa.walks_through("s4").len(), 1);
+        assert!(gfa.walks_through("s3").is_empty());
+
+        assert_eq!(gfa.containments.len(), 1);
+        assert_eq!(gfa.containments_atlas.get("s1").unwrap()[0].pos, 1);
+    }
+
+    #[test]
+    fn test_parse_link_tags() {
+        let gfa = Gfa::parse("test_data/test.gfa").expect("Unable to parse GFA");
+
+        let link = gfa.links.iter().find(|x| x.to == "s4").unwrap();
+        assert_eq!(link.overlap.as_ref().unwrap().reference_length(), 1);
+        assert_eq!(link.read_count.unwrap().get(), 12);
+        assert_eq!(link.mapping_quality, Some(60));
+        assert_eq!(link.mismatches, Some(0));
+        assert_eq!(link.id.as_deref(), Some("l3"));
+        assert_eq!(link.coverage(), Some(12));
+
+        assert!(gfa.links[0].coverage().is_none());
+        assert!(gfa.containments[0].overlap.is_some());
+
+        let fields = ["L", "s1", "+", "s2", "+", "3Q"];
+        let err = overlap(&fields, 5, "overlap").unwrap_err();
+        assert_eq!(err.column, Some(6));
+        assert!(overlap(&["L", "s1", "+", "s2", "+", "*"], 5, "overlap")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_sequences() {
+        let packed = Gfa::parse("test_data/test.gfa").unwrap();
+        let options = ParseOptions {
+            sequences: SequenceStorage::OnDisk,
+            ..Default::default()
+        };
+        let on_disk = Gfa::parse_with("test_data/test.gfa", &options).unwrap();
+
+        for gfa in [&packed, &on_disk] {
+            assert_eq!(
+                gfa.segment_sequence("s3", Orientation::Negative).unwrap(),
+                b"GTTAA"
+            );
+            assert_eq!(gfa.path_sequence("ref").unwrap(), b"ACGTACCGGTTAAC");
+            assert_eq!(
+                gfa.path_sequence("HG002#1#chr1:0-12").unwrap(),
+                b"ACGTACCGGGGC"
+            );
+        }
+
+        let mut out = Vec::new();
+        packed.write_fasta(&mut out, &["ref"]).unwrap();
+        assert_eq!(out, b">ref\nACGTACCGGTTAAC\n");
+
+        let discard = ParseOptions {
+            sequences: SequenceStorage::Discard,
+            ..Default::default()
+        };
+        let gfa = Gfa::parse_with("test_data/test.gfa", &discard).unwrap();
+        assert!(gfa.sequences.is_empty());
+        assert_eq!(*gfa.lengths.get("s1").unwrap(), 5);
+    }
+
+    #[test]
+    fn test_parse_gfa2() {
+        let gfa = Gfa::parse("test_data/test2.gfa").expect("Unable to parse GFA2");
+
+        assert_eq!(gfa.version, GfaVersion::V2);
+        assert_eq!(*gfa.lengths.get("s2").unwrap(), 6);
+        assert_eq!(gfa.segments.get("s1").unwrap().length.unwrap().get(), 8);
+
+        // e1 is a dovetail, e2 is a containment
+        assert_eq!(gfa.links.len(), 1);
+        assert_eq!(gfa.links[0].from, "s1");
+        assert_eq!(gfa.links[0].to, "s2");
+        assert_eq!(gfa.links[0].overlap.as_ref().unwrap().to_string(), "3M");
+        assert_eq!(gfa.links[0].id.as_deref(), Some("e1"));
+        assert_eq!(gfa.containments.len(), 1);
+        assert_eq!(gfa.containments[0].contained, "s3");
+        assert_eq!(gfa.containments[0].pos, 2);
+
+        assert_eq!(gfa.fragments.get("s1").unwrap()[0].external, "read1");
+        assert_eq!(gfa.gaps[0].distance, 100);
+        assert_eq!(gfa.gaps[0].variance, None);
+
+        assert!(!gfa.groups.get("u1").unwrap().ordered);
+        assert_eq!(gfa.paths.get("o1").unwrap().segments.len(), 2);
+        assert_eq!(gfa.paths_through("s2"), &["o1".to_string()]);
+    }
+
+    #[test]
+    fn test_lenient_parse() {
+        assert!(Gfa::parse("test_data/malformed.gfa").is_err());
+
+        let gfa = Gfa::parse_with("test_data/malformed.gfa", &ParseOptions::lenient())
+            .expect("Lenient parsing should skip bad records");
+
+        assert_eq!(gfa.segments.len(), 2);
+        assert_eq!(gfa.links.len(), 1);
+        assert_eq!(gfa.warnings.skipped, 3);
+
+        let err = &gfa.warnings.errors[0];
+        assert_eq!(err.line, Some(3));
+        assert_eq!(err.column, Some(4));
+        assert!(matches!(err.kind, ParseErrorKind::LengthMismatch { .. }));
+
+        let err = &gfa.warnings.errors[1];
+        assert_eq!(err.line, Some(5));
+        assert_eq!(err.field.as_deref(), Some("to_orient"));
+
+        // The first of two paths with the same name is kept
+        let err = &gfa.warnings.errors[2];
+        assert_eq!(err.line, Some(8));
+        assert_eq!(err.field.as_deref(), Some("name"));
+        assert_eq!(gfa.paths["p1"].segments.len(), 2);
+        assert_eq!(gfa.paths_through("s3"), &["p1".to_string()]);
+    }
+
+    #[test]
+    fn test_edge_to_gfa1() {
+        let pos = |x: &str| x.parse::<Position>().unwrap();
+
+        // Overlap at the start of s1 and the end of s2, so s2 leads into s1
+        let edge = edge_to_gfa1(
+            ("s1".to_string(), Orientation::Positive),
+            ("s2".to_string(), Orientation::Positive),
+            [pos("0"), pos("3"), pos("5"), pos("8$")],
+            None,
+        );
+        match edge {
+            Some(Gfa1Edge::Link(link)) => {
+                assert_eq!(link.from, "s2");
+                assert_eq!(link.to, "s1");
+            }
+            _ => panic!("Expected a link"),
+        }
+
+        // Internal alignment
+        assert!(edge_to_gfa1(
+            ("s1".to_string(), Orientation::Positive),
+            ("s2".to_string(), Orientation::Positive),
+            [pos("2"), pos("4"), pos("2"), pos("4")],
+            None,
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_parse_walk_segments() {
+        let walk = parse_walk_segments(">s1<s22>s3").unwrap();
+        assert_eq!(walk.len(), 3);
+        assert_eq!(walk[1].0, "s22");
+        assert!(parse_walk_segments("s1>s2").is_none());
+        assert!(parse_path_segments("s1+,s2").is_none());
+    }
 }
@@ -1,204 +1,557 @@
-use bytelines::*;
 use simdutf8::basic::from_utf8;
 use twox_hash::RandomXxh3HashBuilder64;
 
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::io::BufRead;
 
+use super::bgzf::*;
+use super::error::*;
 use super::feature::*;
+use super::hierarchy::*;
+use super::progress::*;
+use super::tabix::*;
+
+#[derive(Clone, Debug)]
+pub struct Landmark {
+    pub id: String,
+    pub offset: usize, // Position of the first record, a virtual offset in BGZF files
+    pub line: Option<usize>, // Line number of the first record, unknown when taken from an index
+    pub data_length: usize, // (Compressed) bytes of records for this landmark
+    pub length: usize, // Estimated from the largest coordinate seen
+    pub features: usize,
+}
 
 #[derive(Clone, Debug)]
 pub struct Gff3 {
     pub filename: String,
-    pub landmarks: Vec<(String, usize, usize, usize, usize)>, // Landmark name, byte offset, length of data, est. length of landmark, number of features
+    pub landmarks: Vec<Landmark>,
+    pub index: Option<TabixIndex>,
+    pub mode: ParseMode,
+    pub warnings: Warnings,
 }
 
 impl Gff3 {
-    pub fn parse<T>(filename: T) -> Result<Gff3, String>
+    pub fn parse<T>(filename: T) -> Result<Gff3, ParseError>
+    where
+        T: ToString,
+    {
+        Gff3::parse_with(filename, &ParseOptions::default())
+    }
+
+    pub fn parse_with<T>(filename: T, options: &ParseOptions) -> Result<Gff3, ParseError>
     where
         T: ToString,
     {
         let filename = filename.to_string();
+        let (landmarks, index, warnings) = open_landmarks(&filename, options, parse_coordinates)?;
 
-        let mut file = match File::open(&filename) {
-            Ok(x) => BufReader::new(x),
-            Err(_) => return Err(format!("Unable to open file {}", &filename)),
+        Ok(Gff3 {
+            filename,
+            landmarks,
+            index,
+            mode: options.mode,
+            warnings,
+        })
+    }
+
+    /// Skips what the parse mode recovers from, parse_region_with_warnings says what that was
+    pub fn parse_region(&self, landmark: &str) -> Result<Vec<Feature>, ParseError> {
+        let mut warnings = Warnings::default();
+        self.parse_region_with_warnings(landmark, &mut warnings)
+    }
+
+    pub fn parse_region_with_warnings(
+        &self,
+        landmark: &str,
+        warnings: &mut Warnings,
+    ) -> Result<Vec<Feature>, ParseError> {
+        let region = match self.landmarks.iter().find(|x| x.id == landmark) {
+            Some(x) => x,
+            None => return Ok(Vec::new()),
         };
 
-        // let mut lines = BufReader::new(file).byte_lines();
+        let features = read_region(
+            &self.filename,
+            region,
+            self.mode,
+            warnings,
+            Feature::from_gff3_line,
+        )?;
 
-        let mut line_number: usize = 0;
+        Ok(build_hierarchy(features))
+    }
 
-        let mut landmarks: HashMap<String, usize, RandomXxh3HashBuilder64> = Default::default();
-        let mut est_lengths: HashMap<String, usize, RandomXxh3HashBuilder64> = Default::default();
-        let mut num_features: HashMap<String, usize, RandomXxh3HashBuilder64> = Default::default();
+    /// Features overlapping start..=end (1-based), using the tabix index when there is one
+    pub fn parse_range(
+        &self,
+        landmark: &str,
+        start: usize,
+        end: usize,
+        warnings: &mut Warnings,
+    ) -> Result<Vec<Feature>, ParseError> {
+        let region = match self.landmarks.iter().find(|x| x.id == landmark) {
+            Some(x) => x,
+            None => return Ok(Vec::new()),
+        };
 
-        let mut current_offset: usize = 0;
-        let mut current_landmark: String = "".to_string();
+        read_trees(
+            &self.filename,
+            region,
+            self.index.as_ref(),
+            start,
+            end,
+            self.mode,
+            warnings,
+            Feature::from_gff3_line,
+            build_hierarchy,
+        )
+    }
+}
 
-        let mut chr_length: usize = 0;
-        let mut features_count: usize = 0;
+/// Landmark, start and end of a record (1-based, inclusive), the only columns needed to index a
+/// file
+pub(crate) type Coordinates = fn(&str) -> Result<(&str, usize, usize), ParseError>;
 
-        // while let Some(Ok(line)) = lines.next() {
-        let mut line: Vec<u8> = Vec::with_capacity(8192);
-        let mut bytes_read = 42;
+/// What reading a landmark's records needs to know of each one
+pub(crate) trait Record {
+    fn landmark(&self) -> &str;
+    fn range(&self) -> (usize, usize); // 1-based, inclusive
+}
 
-        while bytes_read > 0 {
-            line.clear();
-            current_offset = file.stream_position().expect("Seek API is broken") as usize;
-            line_number += 1;
+impl Record for Feature {
+    fn landmark(&self) -> &str {
+        &self.landmark
+    }
 
-            if let Ok(bytes) = file.read_until(b'\n', &mut line) {
-                bytes_read = bytes;
-            } else {
-                return Err(format!("Error parsing file {}", &filename));
-            }
+    fn range(&self) -> (usize, usize) {
+        (self.start, self.end)
+    }
+}
 
-            // EOF
-            if bytes_read == 0 {
-                break;
+/// Landmarks from the tabix index when there is one, otherwise from a scan of the whole file
+pub(crate) fn open_landmarks(
+    filename: &str,
+    options: &ParseOptions,
+    coordinates: Coordinates,
+) -> Result<(Vec<Landmark>, Option<TabixIndex>, Warnings), ParseError> {
+    if let Some(index_filename) = TabixIndex::find(filename) {
+        let index = TabixIndex::read(&index_filename)?;
+        let landmarks = landmarks_from_index(&index);
+        return Ok((landmarks, Some(index), Warnings::default()));
+    }
+
+    let (landmarks, warnings) = index_landmarks(filename, options, coordinates)?;
+    Ok((landmarks, None, warnings))
+}
+
+fn landmarks_from_index(index: &TabixIndex) -> Vec<Landmark> {
+    let mut landmarks = Vec::with_capacity(index.names.len());
+
+    for (name, reference) in index.names.iter().zip(index.references.iter()) {
+        let start = match index.reference_start(name) {
+            Some(x) => x,
+            None => continue, // No records
+        };
+
+        let data_length = match reference.end {
+            Some(end) => end.compressed().saturating_sub(start.compressed()) as usize,
+            None => 0,
+        };
+
+        landmarks.push(Landmark {
+            id: name.clone(),
+            offset: start.0 as usize,
+            line: None,
+            data_length,
+            length: index.estimated_length(name),
+            features: reference.mapped.unwrap_or_default() as usize,
+        });
+    }
+
+    landmarks.sort_by_key(|x| x.data_length);
+    landmarks.reverse();
+    landmarks
+}
+
+/// Scans a tab separated file (GFF3, GTF, VCF) for where each landmark's records start. Records
+/// have to be grouped by landmark, as they are in sorted files.
+pub(crate) fn index_landmarks(
+    filename: &str,
+    options: &ParseOptions,
+    coordinates: Coordinates,
+) -> Result<(Vec<Landmark>, Warnings), ParseError> {
+    let mut file = match InputFile::open(filename) {
+        Ok(x) => x,
+        Err(err) => return Err(ParseError::from(err).with_file(filename)),
+    };
+
+    // Positions are virtual offsets in BGZF files, only the compressed part says anything
+    // about size
+    let bgzf = file.compression() == Compression::Bgzf;
+    let size_of = |x: usize| if bgzf { x >> 16 } else { x };
+
+    let mut line_number: usize = 0;
+    let mut warnings = Warnings::default();
+
+    let mut landmarks: HashMap<String, (usize, usize), RandomXxh3HashBuilder64> =
+        Default::default();
+    let mut est_lengths: HashMap<String, usize, RandomXxh3HashBuilder64> = Default::default();
+    let mut num_features: HashMap<String, usize, RandomXxh3HashBuilder64> = Default::default();
+
+    let mut current_offset: usize = 0;
+    let mut current_landmark: String = "".to_string();
+
+    let mut chr_length: usize = 0;
+    let mut features_count: usize = 0;
+
+    let mut line: Vec<u8> = Vec::with_capacity(8192);
+    let mut bytes_read = 42;
+
+    if let Some(progress) = &options.progress {
+        progress.start(filename);
+    }
+
+    while bytes_read > 0 {
+        line.clear();
+        current_offset = file.position() as usize;
+        line_number += 1;
+
+        if line_number % PROGRESS_LINES == 0 {
+            if let Some(progress) = &options.progress {
+                progress.update(&file);
             }
+        }
 
-            // Skip blank lines
-            if line.is_empty() {
-                continue;
+        bytes_read = match file.read_until(b'\n', &mut line) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                return Err(ParseError::from(err)
+                    .at_line(line_number)
+                    .with_file(filename))
             }
+        };
 
-            // Skip comments (TODO: Maybe store them in the future though? For context?)
-            if line[0] == b'#' {
+        // EOF
+        if bytes_read == 0 {
+            break;
+        }
+
+        // Skip blank lines
+        if line.is_empty() {
+            continue;
+        }
+
+        // Everything after ##FASTA is sequence
+        if line.starts_with(b"##FASTA") {
+            break;
+        }
+
+        // Skip comments (TODO: Maybe store them in the future though? For context?)
+        if line[0] == b'#' {
+            continue;
+        }
+
+        let line = match from_utf8(&line) {
+            Ok(x) => x.trim(),
+            Err(_) => {
+                let err = ParseError::new(ParseErrorKind::InvalidUtf8)
+                    .at_line(line_number)
+                    .with_file(filename);
+                options.mode.recover(err, &mut warnings)?;
                 continue;
             }
+        };
 
-            let line = match from_utf8(&line) {
-                Ok(x) => x.trim(),
-                Err(err) => {
-                    // TODO: Formal logging library...
-                    println!(
-                        "Error while parsing GFF File -- Line {} skipped: {}",
-                        line_number, err
-                    );
-                    continue;
-                }
-            };
+        // After trimming, is the line blank?
+        if line.is_empty() {
+            continue;
+        }
 
-            // After trimming, is the line blank?
-            if line.is_empty() {
+        // Parse the lines to identify Landmark starting sites (and landmarks)
+        let (landmark, start, end) = match coordinates(line) {
+            Ok(x) => x,
+            Err(err) => {
+                let err = err.at_line(line_number).with_file(filename);
+                options.mode.recover(err, &mut warnings)?;
                 continue;
             }
+        };
 
-            // Parse GFF3 Lines to identify Landmark starting sites (and landmarks)
-            // TODO: This will need much nicer error handling...
-            let line_parsed: Vec<&str> = line.splitn(6, '\t').collect();
-            let landmark = line_parsed[0];
-            let start = line_parsed[3]
-                .parse::<usize>()
-                .expect("Invalid start position");
-            let end = line_parsed[4]
-                .parse::<usize>()
-                .expect("Invalid end position");
-
-            //if let Some((landmark, _)) = line.split_once("\t") {
-            if landmark != &current_landmark {
-                println!("{} {}", landmark, current_offset);
-                landmarks.insert(landmark.to_string(), current_offset);
-
-                if !current_landmark.is_empty() {
-                    num_features.insert(current_landmark.to_string(), features_count);
-                    est_lengths.insert(current_landmark.to_string(), chr_length);
-                    features_count = 0;
-                    chr_length = 0;
-                }
-                current_landmark = landmark.to_string();
-            }
+        if landmark != current_landmark {
+            landmarks.insert(landmark.to_string(), (current_offset, line_number));
 
-            features_count = features_count.saturating_add(1);
-            chr_length = std::cmp::max(chr_length, start);
-            chr_length = std::cmp::max(chr_length, end);
+            if !current_landmark.is_empty() {
+                num_features.insert(current_landmark.to_string(), features_count);
+                est_lengths.insert(current_landmark.to_string(), chr_length);
+                features_count = 0;
+                chr_length = 0;
+            }
+            current_landmark = landmark.to_string();
         }
 
-        num_features.insert(current_landmark.to_string(), features_count);
-        est_lengths.insert(current_landmark.to_string(), chr_length);
+        features_count = features_count.saturating_add(1);
+        chr_length = std::cmp::max(chr_length, start);
+        chr_length = std::cmp::max(chr_length, end);
+    }
 
-        let mut landmarks: Vec<(String, usize)> = landmarks.drain().collect();
-        landmarks.sort_by_key(|x| x.1);
+    num_features.insert(current_landmark.to_string(), features_count);
+    est_lengths.insert(current_landmark.to_string(), chr_length);
 
-        let mut landmarks_final_vec = Vec::with_capacity(landmarks.len());
-        let mut lengths = Vec::with_capacity(landmarks.len());
+    if let Some(progress) = &options.progress {
+        progress.finish();
+    }
 
-        for x in landmarks.windows(2) {
-            let len = x[1].1 - x[0].1;
-            lengths.push(len);
-        }
+    let mut landmarks: Vec<(String, (usize, usize))> = landmarks.drain().collect();
+    landmarks.sort_by_key(|x| x.1);
 
-        lengths.push(current_offset - landmarks.last().unwrap().1);
+    let mut landmarks_final_vec = Vec::with_capacity(landmarks.len());
+    let mut lengths = Vec::with_capacity(landmarks.len());
+
+    for x in landmarks.windows(2) {
+        let len = size_of(x[1].1 .0) - size_of(x[0].1 .0);
+        lengths.push(len);
+    }
+
+    if let Some(last) = landmarks.last() {
+        lengths.push(size_of(current_offset) - size_of(last.1 .0));
+    }
 
-        for (n, x) in landmarks.into_iter().enumerate() {
-            println!("Looking for... {}", x.0);
-            let chr_size = *est_lengths.get(&x.0).unwrap();
-            let num_feat = *num_features.get(&x.0).unwrap();
+    for (n, (id, (offset, line))) in landmarks.into_iter().enumerate() {
+        let length = est_lengths.get(&id).copied().unwrap_or_default();
+        let features = num_features.get(&id).copied().unwrap_or_default();
+
+        landmarks_final_vec.push(Landmark {
+            id,
+            offset,
+            line: Some(line),
+            data_length: lengths[n],
+            length,
+            features,
+        });
+    }
+
+    landmarks_final_vec.sort_by_key(|x| x.data_length);
+    landmarks_final_vec.reverse();
+
+    Ok((landmarks_final_vec, warnings))
+}
+
+/// Reads every record of a landmark, starting at its indexed offset
+pub(crate) fn read_region<T: Record>(
+    filename: &str,
+    region: &Landmark,
+    mode: ParseMode,
+    warnings: &mut Warnings,
+    parse_line: fn(&str) -> Result<T, ParseError>,
+) -> Result<Vec<T>, ParseError> {
+    let mut file = match InputFile::open(filename) {
+        Ok(x) => x,
+        Err(err) => return Err(ParseError::from(err).with_file(filename)),
+    };
+
+    let mut features = Vec::new();
+
+    if let Err(err) = file.seek_to(region.offset as u64) {
+        return Err(ParseError::from(err).with_file(filename));
+    }
+
+    let reader = RecordReader {
+        filename,
+        landmark: &region.id,
+        mode,
+        parse_line,
+    };
+    // Counted up as lines are read
+    let line_number = region.line.map(|x| x - 1);
+    reader.read(&mut file, None, line_number, warnings, &mut features)?;
+
+    Ok(features)
+}
 
-            landmarks_final_vec.push((x.0, x.1, lengths[n], chr_size, num_feat));
+/// Records of a landmark in the chunks the tabix index points to for start..=end (1-based, like
+/// the features), without one the whole landmark. Some may not overlap the range.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn read_range<T: Record>(
+    filename: &str,
+    region: &Landmark,
+    index: Option<&TabixIndex>,
+    start: usize,
+    end: usize,
+    mode: ParseMode,
+    warnings: &mut Warnings,
+    parse_line: fn(&str) -> Result<T, ParseError>,
+) -> Result<Vec<T>, ParseError> {
+    let mut features = match index {
+        Some(index) => {
+            let mut file = match InputFile::open(filename) {
+                Ok(x) => x,
+                Err(err) => return Err(ParseError::from(err).with_file(filename)),
+            };
+
+            let reader = RecordReader {
+                filename,
+                landmark: &region.id,
+                mode,
+                parse_line,
+            };
+
+            let mut features = Vec::new();
+            for chunk in index.query(&region.id, start.saturating_sub(1), end) {
+                if let Err(err) = file.seek_to(chunk.start.0) {
+                    return Err(ParseError::from(err).with_file(filename));
+                }
+                reader.read(&mut file, Some(chunk.end.0), None, warnings, &mut features)?;
+            }
+            features
         }
+        None => read_region(filename, region, mode, warnings, parse_line)?,
+    };
 
-        landmarks_final_vec.sort_by_key(|x| x.2);
-        landmarks_final_vec.reverse();
+    Ok(features)
+}
 
-        Ok(Gff3 {
-            filename,
-            landmarks: landmarks_final_vec,
-        })
+/// Top-level features overlapping start..=end, with all of their parts. Parts of a gene reaching
+/// past the range can be in chunks the range doesn't point to, so the range is widened to the
+/// genes found and read again until it covers them.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn read_trees(
+    filename: &str,
+    region: &Landmark,
+    index: Option<&TabixIndex>,
+    start: usize,
+    end: usize,
+    mode: ParseMode,
+    warnings: &mut Warnings,
+    parse_line: fn(&str) -> Result<Feature, ParseError>,
+    assemble: fn(Vec<Feature>) -> Vec<Feature>,
+) -> Result<Vec<Feature>, ParseError> {
+    let (mut from, mut to) = (start, end);
+
+    loop {
+        // Only the last read's warnings are kept, the earlier ones read the same lines
+        let mut read = Warnings::default();
+        let features = read_range(
+            filename, region, index, from, to, mode, &mut read, parse_line,
+        )?;
+
+        let mut features = assemble(features);
+        features.retain(|x| x.start <= end && start <= x.end);
+
+        let first = features.iter().map(|x| x.start).fold(from, usize::min);
+        let last = features.iter().map(|x| x.end).fold(to, usize::max);
+
+        // Without an index the whole landmark was read already
+        if index.is_none() || (first, last) == (from, to) {
+            warnings.append(read);
+            return Ok(features);
+        }
+
+        from = first;
+        to = last;
     }
+}
+
+struct RecordReader<'a, T> {
+    filename: &'a str,
+    landmark: &'a str,
+    mode: ParseMode,
+    parse_line: fn(&str) -> Result<T, ParseError>,
+}
 
-    pub fn parse_region(&self, landmark: &str) -> Result<Vec<Feature>, String> {
-        let mut file = match File::open(&self.filename) {
-            Ok(x) => BufReader::new(x),
-            Err(_) => return Err(format!("Unable to open file {}", &self.filename)),
+impl<T: Record> RecordReader<'_, T> {
+    // Reads from the current position until the landmark changes, or until the end position
+    // (exclusive) is reached. Line numbers are only known when reading from a scanned offset.
+    fn read(
+        &self,
+        file: &mut InputFile,
+        end: Option<u64>,
+        mut line_number: Option<usize>,
+        warnings: &mut Warnings,
+        features: &mut Vec<T>,
+    ) -> Result<(), ParseError> {
+        let locate = |err: ParseError, line_number: Option<usize>| match line_number {
+            Some(x) => err.at_line(x).with_file(self.filename),
+            None => err.with_file(self.filename),
         };
 
-        let mut features = Vec::new();
+        let mut line: Vec<u8> = Vec::with_capacity(8192);
+
+        loop {
+            if matches!(end, Some(end) if file.position() >= end) {
+                break;
+            }
 
-        for (id, pos, _l, _, _) in self.landmarks.iter() {
-            if id == landmark {
-                println!("Seeking!");
-                file.seek(SeekFrom::Start(*pos as u64))
-                    .expect("Seek IO not working!");
+            line.clear();
+            let bytes_read = match file.read_until(b'\n', &mut line) {
+                Ok(x) => x,
+                Err(err) => return Err(locate(ParseError::from(err), line_number)),
+            };
+
+            // EOF
+            if bytes_read == 0 {
                 break;
             }
-        }
 
-        let mut lines = file.byte_lines();
+            line_number = line_number.map(|x| x + 1);
 
-        while let Some(line) = lines.next() {
-            let line = line.unwrap();
+            if line.starts_with(b"##FASTA") {
+                break;
+            }
 
             if line[0] == b'#' {
                 continue;
             }
 
-            let x = match from_utf8(line) {
+            let x = match from_utf8(&line) {
                 Ok(x) => x.trim(),
-                Err(err) => {
-                    println!("Unable to parse a line from GFF3 file... {}", err);
+                Err(_) => {
+                    let err = ParseError::new(ParseErrorKind::InvalidUtf8);
+                    self.mode.recover(locate(err, line_number), warnings)?;
                     continue;
                 }
             };
 
-            let feat = Feature::from_gff3_line(x).unwrap();
-            if feat.landmark == landmark {
-                if feat.feature_type == "gene" {
-                    features.push(feat);
+            if x.is_empty() {
+                continue;
+            }
+
+            let feat = match (self.parse_line)(x) {
+                Ok(x) => x,
+                Err(err) => {
+                    self.mode.recover(locate(err, line_number), warnings)?;
+                    continue;
                 }
-            } else {
-                println!("All done... {} {}", feat.landmark, landmark);
+            };
+
+            if feat.landmark() == self.landmark {
+                features.push(feat);
+            } else if end.is_none() {
                 break;
             }
         }
 
-        println!("Parsed {} ", features.len());
+        Ok(())
+    }
+}
+
+// For GFF3 and GTF
+pub(crate) fn parse_coordinates(line: &str) -> Result<(&str, usize, usize), ParseError> {
+    let line_parsed: Vec<&str> = line.splitn(6, '\t').collect();
 
-        Ok(features)
+    if line_parsed.len() < 5 {
+        return Err(
+            ParseError::new(ParseErrorKind::MissingField).with_field(line_parsed.len() + 1, "end")
+        );
     }
+
+    let start = line_parsed[3]
+        .parse::<usize>()
+        .map_err(|_| ParseError::new(ParseErrorKind::InvalidInteger).with_field(4, "start"))?;
+    let end = line_parsed[4]
+        .parse::<usize>()
+        .map_err(|_| ParseError::new(ParseErrorKind::InvalidInteger).with_field(5, "end"))?;
+
+    Ok((line_parsed[0], start, end))
 }
 
 #[cfg(test)]
@@ -212,6 +565,70 @@ mod tests {
         panic!("Ok");
     }
 
+    #[test]
+    fn test_parse_malformed_gff3() {
+        let err = Gff3::parse("test_data/malformed.gff3").unwrap_err();
+        assert_eq!(err.line, Some(4));
+        assert_eq!(err.column, Some(4));
+        assert_eq!(err.kind, ParseErrorKind::InvalidInteger);
+
+        let gff3 = Gff3::parse_with("test_data/malformed.gff3", &ParseOptions::lenient())
+            .expect("Lenient parsing should skip bad records");
+        assert_eq!(gff3.warnings.skipped, 1);
+        assert_eq!(gff3.landmarks.len(), 2);
+        assert_eq!(gff3.landmarks[0].features, 2);
+
+        let mut warnings = Warnings::default();
+        let features = gff3
+            .parse_region_with_warnings("chr1", &mut warnings)
+            .unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0].subfeatures.as_ref().unwrap().len(), 1);
+        assert_eq!(warnings.errors[0].line, Some(4));
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let plain = Gff3::parse("test_data/sorted.gff3").unwrap();
+        let indexed = Gff3::parse("test_data/sorted.gff3.gz").unwrap();
+        assert!(plain.index.is_none());
+        assert!(indexed.index.is_some());
+
+        assert_eq!(indexed.landmarks.len(), 2);
+        assert_eq!(indexed.landmarks[0].features, 5);
+
+        // Whole landmarks read the same through virtual offsets
+        for landmark in ["chr1", "chr2"] {
+            assert_eq!(
+                plain.parse_region(landmark).unwrap(),
+                indexed.parse_region(landmark).unwrap()
+            );
+        }
+
+        let mut warnings = Warnings::default();
+        for gff3 in [&plain, &indexed] {
+            let genes = gff3
+                .parse_range("chr2", 20100, 21000, &mut warnings)
+                .unwrap();
+            assert_eq!(genes.len(), 1);
+            assert_eq!(genes[0].name, "gene3");
+            assert_eq!(genes[0].children().len(), 1);
+
+            assert!(gff3
+                .parse_range("chr2", 1000, 2000, &mut warnings)
+                .unwrap()
+                .is_empty());
+
+            // Cutting through a gene still gives all of it
+            let genes = gff3.parse_range("chr1", 150, 300, &mut warnings).unwrap();
+            assert_eq!(genes.len(), 1);
+            let mrna = &genes[0].children()[0];
+            assert_eq!(mrna.children().len(), 3);
+            assert!(mrna.children().iter().any(|x| x.start == 600));
+        }
+        assert!(warnings.is_empty());
+    }
+
     #[test]
     fn test_parse_large_gff3() {
         let j = Gff3::parse("test_data/kakapo_large.gff3");
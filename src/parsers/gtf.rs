@@ -0,0 +1,333 @@
+use twox_hash::RandomXxh3HashBuilder64;
+
+use std::collections::{HashMap, HashSet};
+
+use super::attributes::*;
+use super::error::*;
+use super::feature::*;
+use super::gff3::*;
+use super::hierarchy::*;
+use super::tabix::*;
+
+// GTF (GFF2 with gene_id/transcript_id attributes) from Ensembl, GENCODE, StringTie, etc...
+#[derive(Clone, Debug)]
+pub struct Gtf {
+    pub filename: String,
+    pub landmarks: Vec<Landmark>,
+    pub index: Option<TabixIndex>,
+    pub mode: ParseMode,
+    pub warnings: Warnings,
+}
+
+impl Gtf {
+    pub fn parse<T>(filename: T) -> Result<Gtf, ParseError>
+    where
+        T: ToString,
+    {
+        Gtf::parse_with(filename, &ParseOptions::default())
+    }
+
+    pub fn parse_with<T>(filename: T, options: &ParseOptions) -> Result<Gtf, ParseError>
+    where
+        T: ToString,
+    {
+        let filename = filename.to_string();
+        let (landmarks, index, warnings) = open_landmarks(&filename, options, parse_coordinates)?;
+
+        Ok(Gtf {
+            filename,
+            landmarks,
+            index,
+            mode: options.mode,
+            warnings,
+        })
+    }
+
+    /// Skips what the parse mode recovers from, parse_region_with_warnings says what that was
+    pub fn parse_region(&self, landmark: &str) -> Result<Vec<Feature>, ParseError> {
+        let mut warnings = Warnings::default();
+        self.parse_region_with_warnings(landmark, &mut warnings)
+    }
+
+    pub fn parse_region_with_warnings(
+        &self,
+        landmark: &str,
+        warnings: &mut Warnings,
+    ) -> Result<Vec<Feature>, ParseError> {
+        let region = match self.landmarks.iter().find(|x| x.id == landmark) {
+            Some(x) => x,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut features = read_region(
+            &self.filename,
+            region,
+            self.mode,
+            warnings,
+            Feature::from_gtf_line,
+        )?;
+
+        add_implicit_parents(&mut features);
+
+        Ok(build_hierarchy(features))
+    }
+
+    /// Features overlapping start..=end (1-based), using the tabix index when there is one
+    pub fn parse_range(
+        &self,
+        landmark: &str,
+        start: usize,
+        end: usize,
+        warnings: &mut Warnings,
+    ) -> Result<Vec<Feature>, ParseError> {
+        let region = match self.landmarks.iter().find(|x| x.id == landmark) {
+            Some(x) => x,
+            None => return Ok(Vec::new()),
+        };
+
+        read_trees(
+            &self.filename,
+            region,
+            self.index.as_ref(),
+            start,
+            end,
+            self.mode,
+            warnings,
+            Feature::from_gtf_line,
+            |mut features| {
+                add_implicit_parents(&mut features);
+                build_hierarchy(features)
+            },
+        )
+    }
+}
+
+impl Feature {
+    /// GTF lines become GFF3-style features, with ID and Parent derived from gene_id and
+    /// transcript_id so they assemble into the same gene -> transcript -> exon trees.
+    pub fn from_gtf_line(line: &str) -> Result<Feature, ParseError> {
+        let (columns, attributes) = match line.rsplit_once('\t') {
+            Some((columns, attributes)) if columns.matches('\t').count() == 7 => {
+                (columns, attributes)
+            }
+            _ => (line, ""),
+        };
+
+        let mut feature = Feature::from_gff3_line(columns)?;
+        let mut attributes = parse_gtf_attributes(attributes)?;
+
+        let gene_id = first(&attributes, "gene_id");
+        let transcript_id = first(&attributes, "transcript_id");
+
+        match feature.feature_type.as_str() {
+            "gene" => {
+                attributes.id = gene_id.as_ref().map(|x| format!("gene:{}", x));
+                attributes.name = first(&attributes, "gene_name").or(gene_id);
+            }
+            "transcript" => {
+                attributes.id = transcript_id.as_ref().map(|x| format!("transcript:{}", x));
+                attributes.name = first(&attributes, "transcript_name").or(transcript_id);
+                attributes
+                    .parent
+                    .extend(gene_id.map(|x| format!("gene:{}", x)));
+            }
+            _ => {
+                attributes.name = first(&attributes, "exon_id");
+                match (transcript_id, gene_id) {
+                    (Some(x), _) => attributes.parent.push(format!("transcript:{}", x)),
+                    (None, Some(x)) => attributes.parent.push(format!("gene:{}", x)),
+                    (None, None) => (),
+                }
+            }
+        }
+
+        feature.name = attributes.name.clone().unwrap_or_default();
+        feature.attributes = attributes;
+
+        Ok(feature)
+    }
+}
+
+fn first(attributes: &Attributes, key: &str) -> Option<String> {
+    attributes.other.get(key).and_then(|x| x.first()).cloned()
+}
+
+// gene_id "ENSG00000223972"; transcript_id "ENST00000456328"; exon_number 1; tag "basic";
+fn parse_gtf_attributes(column: &str) -> Result<Attributes, ParseError> {
+    let mut attributes = Attributes::default();
+    let invalid = || ParseError::new(ParseErrorKind::InvalidValue).with_field(9, "attributes");
+
+    let mut rest = column.trim();
+    if rest == "." {
+        return Ok(attributes);
+    }
+
+    while !rest.is_empty() {
+        let (key, after_key) = match rest.split_once(|x: char| x.is_ascii_whitespace()) {
+            Some(x) => x,
+            None => return Err(invalid()),
+        };
+        let after_key = after_key.trim_start();
+
+        // Quoted values may contain ; so they're read up to the closing quote
+        let (value, after_value) = if let Some(quoted) = after_key.strip_prefix('"') {
+            match quoted.split_once('"') {
+                Some(x) => x,
+                None => return Err(invalid()),
+            }
+        } else {
+            after_key.split_once(';').unwrap_or((after_key, ""))
+        };
+
+        attributes
+            .other
+            .entry(key.to_string())
+            .or_default()
+            .push(value.trim().to_string());
+
+        rest = after_value.trim_start();
+        rest = rest.strip_prefix(';').unwrap_or(rest).trim_start();
+    }
+
+    Ok(attributes)
+}
+
+// StringTie and GTF 2.2 files often only list exons and CDS, so genes and transcripts are
+// synthesized to span their children
+fn add_implicit_parents(features: &mut Vec<Feature>) {
+    let present = features
+        .iter()
+        .filter_map(|x| x.attributes.id.clone())
+        .collect::<HashSet<String, RandomXxh3HashBuilder64>>();
+
+    let mut transcripts: HashMap<String, Feature, RandomXxh3HashBuilder64> = Default::default();
+    let mut genes: HashMap<String, Feature, RandomXxh3HashBuilder64> = Default::default();
+
+    for feature in features.iter() {
+        let gene_id = first(&feature.attributes, "gene_id");
+        let transcript_id = first(&feature.attributes, "transcript_id");
+
+        if feature.feature_type != "transcript" && feature.feature_type != "gene" {
+            if let Some(transcript_id) = &transcript_id {
+                let id = format!("transcript:{}", transcript_id);
+                if !present.contains(&id) {
+                    let transcript = transcripts.entry(id.clone()).or_insert_with(|| {
+                        let mut transcript = implicit_parent(feature, "transcript", &id);
+                        transcript.name = first(&feature.attributes, "transcript_name")
+                            .unwrap_or_else(|| transcript_id.clone());
+                        transcript
+                            .attributes
+                            .parent
+                            .extend(gene_id.as_ref().map(|x| format!("gene:{}", x)));
+                        transcript
+                    });
+                    extend(transcript, feature);
+                }
+            }
+        }
+
+        if feature.feature_type != "gene" {
+            if let Some(gene_id) = &gene_id {
+                let id = format!("gene:{}", gene_id);
+                if !present.contains(&id) {
+                    let gene = genes.entry(id.clone()).or_insert_with(|| {
+                        let mut gene = implicit_parent(feature, "gene", &id);
+                        gene.name = first(&feature.attributes, "gene_name")
+                            .unwrap_or_else(|| gene_id.clone());
+                        gene
+                    });
+                    extend(gene, feature);
+                }
+            }
+        }
+    }
+
+    features.extend(transcripts.into_values());
+    features.extend(genes.into_values());
+}
+
+fn implicit_parent(child: &Feature, feature_type: &str, id: &str) -> Feature {
+    let mut attributes = Attributes {
+        id: Some(id.to_string()),
+        ..Default::default()
+    };
+
+    for key in ["gene_id", "gene_name", "transcript_id", "transcript_name"] {
+        if let Some(x) = child.attributes.other.get(key) {
+            if feature_type == "transcript" || key.starts_with("gene") {
+                attributes.other.insert(key.to_string(), x.clone());
+            }
+        }
+    }
+
+    Feature {
+        landmark: child.landmark.clone(),
+        source: child.source.clone(),
+        start: child.start,
+        end: child.end,
+        feature_type: feature_type.to_string(),
+        strand: child.strand,
+        attributes,
+        ..Default::default()
+    }
+}
+
+fn extend(parent: &mut Feature, child: &Feature) {
+    parent.start = std::cmp::min(parent.start, child.start);
+    parent.end = std::cmp::max(parent.end, child.end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gtf_attributes() {
+        let attributes = parse_gtf_attributes(
+            "gene_id \"ENSG1\"; transcript_id \"ENST1\"; exon_number 2; tag \"basic\"; tag \"a;b\";",
+        )
+        .unwrap();
+
+        assert_eq!(first(&attributes, "gene_id").as_deref(), Some("ENSG1"));
+        assert_eq!(first(&attributes, "exon_number").as_deref(), Some("2"));
+        assert_eq!(attributes.get("tag").unwrap(), vec!["basic", "a;b"]);
+
+        assert!(parse_gtf_attributes("gene_id \"ENSG1").is_err());
+    }
+
+    #[test]
+    fn test_parse_region() {
+        let gtf = Gtf::parse("test_data/test.gtf").expect("Unable to parse GTF");
+        assert_eq!(gtf.landmarks.len(), 2);
+
+        // chr1 has explicit gene and transcript lines
+        let genes = gtf.parse_region("chr1").unwrap();
+        assert_eq!(genes.len(), 1);
+        assert_eq!(genes[0].name, "DDX11L1");
+        assert_eq!(genes[0].attributes.id.as_deref(), Some("gene:ENSG1"));
+        let transcript = &genes[0].children()[0];
+        assert_eq!(transcript.feature_type, "transcript");
+        assert_eq!(transcript.children().len(), 3);
+
+        // chr2 is StringTie-style, exons only
+        let genes = gtf.parse_region("chr2").unwrap();
+        assert_eq!(genes.len(), 1);
+        assert_eq!(genes[0].feature_type, "gene");
+        assert_eq!((genes[0].start, genes[0].end), (100, 900));
+        assert_eq!(genes[0].children().len(), 2);
+        assert_eq!(genes[0].children()[0].children().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_gzip() {
+        let plain = Gtf::parse("test_data/test.gtf").unwrap();
+        let gtf = Gtf::parse("test_data/test.gtf.gz").unwrap();
+
+        for landmark in ["chr1", "chr2"] {
+            assert_eq!(
+                plain.parse_region(landmark).unwrap(),
+                gtf.parse_region(landmark).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use twox_hash::RandomXxh3HashBuilder64;
+
+use std::collections::HashMap;
+
+use super::feature::*;
+
+/// Assembles the ID/Parent tree for the features of a single landmark and returns the roots.
+///
+/// Lines sharing an ID are parts of one discontinuous feature (e.g. a CDS split over exons), each
+/// part is kept as its own subfeature. Children of a discontinuous parent go to the part they
+/// overlap. Features with several parents are copied under each of them, and features whose
+/// parents can't be found are promoted to roots rather than dropped.
+pub fn build_hierarchy(features: Vec<Feature>) -> Vec<Feature> {
+    let mut by_id: HashMap<&str, Vec<usize>, RandomXxh3HashBuilder64> = Default::default();
+    for (n, feature) in features.iter().enumerate() {
+        if let Some(id) = feature.attributes.id.as_deref() {
+            by_id.entry(id).or_default().push(n);
+        }
+    }
+
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); features.len()];
+    let mut roots: Vec<usize> = Vec::new();
+
+    for (n, feature) in features.iter().enumerate() {
+        let mut attached = false;
+
+        for parent in feature.attributes.parent.iter() {
+            let parts = match by_id.get(parent.as_str()) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            let part = parts
+                .iter()
+                .copied()
+                .find(|&x| overlaps(&features[x], feature))
+                .unwrap_or(parts[0]);
+
+            if part != n && !children[part].contains(&n) {
+                children[part].push(n);
+                attached = true;
+            }
+        }
+
+        // No parents, or orphaned
+        if !attached {
+            roots.push(n);
+        }
+    }
+
+    let mut stack = Vec::new();
+    let mut visited = vec![false; features.len()];
+    let mut tree = roots
+        .into_iter()
+        .map(|n| assemble(n, &features, &children, &mut stack, &mut visited))
+        .collect::<Vec<Feature>>();
+
+    // Features caught in a Parent loop are unreachable from any root, so break the loop
+    for n in 0..features.len() {
+        if !visited[n] {
+            tree.push(assemble(n, &features, &children, &mut stack, &mut visited));
+        }
+    }
+
+    tree.sort_by_key(|x| x.start);
+    tree
+}
+
+fn assemble(
+    n: usize,
+    features: &[Feature],
+    children: &[Vec<usize>],
+    stack: &mut Vec<usize>,
+    visited: &mut [bool],
+) -> Feature {
+    let mut feature = features[n].clone();
+
+    visited[n] = true;
+    stack.push(n);
+
+    let mut subfeatures = Vec::with_capacity(children[n].len());
+    for &child in children[n].iter() {
+        // Parent loops in the input would otherwise never end
+        if !stack.contains(&child) {
+            subfeatures.push(assemble(child, features, children, stack, visited));
+        }
+    }
+
+    stack.pop();
+
+    if !subfeatures.is_empty() {
+        subfeatures.sort_by_key(|x| x.start);
+        feature.subfeatures = Some(subfeatures);
+    }
+
+    feature
+}
+
+fn overlaps(a: &Feature, b: &Feature) -> bool {
+    a.start <= b.end && b.start <= a.end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(lines: &[&str]) -> Vec<Feature> {
+        lines
+            .iter()
+            .map(|x| Feature::from_gff3_line(x).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_build_hierarchy() {
+        let roots = build_hierarchy(features(&[
+            "chr1\t.\tgene\t100\t900\t.\t+\t.\tID=gene1",
+            "chr1\t.\tmRNA\t100\t900\t.\t+\t.\tID=mrna1;Parent=gene1",
+            "chr1\t.\tmRNA\t100\t700\t.\t+\t.\tID=mrna2;Parent=gene1",
+            "chr1\t.\texon\t100\t200\t.\t+\t.\tParent=mrna1,mrna2",
+            "chr1\t.\texon\t600\t900\t.\t+\t.\tParent=mrna1",
+            "chr1\t.\tCDS\t150\t200\t.\t+\t0\tID=cds1;Parent=mrna1",
+            "chr1\t.\tCDS\t600\t800\t.\t+\t1\tID=cds1;Parent=mrna1",
+            "chr1\t.\texon\t50\t80\t.\t+\t.\tParent=missing",
+        ]));
+
+        assert_eq!(roots.len(), 2);
+        assert_eq!(roots[0].feature_type, "exon"); // The orphan, sorted by start
+
+        let gene = &roots[1];
+        let mrnas = gene.subfeatures.as_ref().unwrap();
+        assert_eq!(mrnas.len(), 2);
+
+        // Both parts of cds1 plus two exons
+        let mrna1 = mrnas.iter().find(|x| x.name == "mrna1").unwrap();
+        assert_eq!(mrna1.subfeatures.as_ref().unwrap().len(), 4);
+
+        // The shared exon is copied under both parents
+        let mrna2 = mrnas.iter().find(|x| x.name == "mrna2").unwrap();
+        assert_eq!(mrna2.subfeatures.as_ref().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parent_loop() {
+        let roots = build_hierarchy(features(&[
+            "chr1\t.\tgene\t100\t900\t.\t+\t.\tID=a;Parent=b",
+            "chr1\t.\tgene\t100\t900\t.\t+\t.\tID=b;Parent=a",
+        ]));
+
+        // Neither is a root, the loop is broken at the first one
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].name, "a");
+        assert_eq!(roots[0].subfeatures.as_ref().unwrap()[0].name, "b");
+    }
+}
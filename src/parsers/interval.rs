@@ -0,0 +1,326 @@
+// Implicit augmented interval tree, same layout as cgranges (https://github.com/lh3/cgranges):
+// intervals sorted by start form a complete binary tree in the array itself, every node keeps
+// the largest end of its subtree.
+
+use super::feature::Feature;
+
+#[derive(Clone, Debug)]
+struct Interval<T> {
+    start: usize,
+    end: usize,
+    max: usize, // Largest end in the subtree rooted here
+    value: T,
+}
+
+/// Intervals are 0-based, half-open [start, end)
+#[derive(Clone, Debug)]
+pub struct IntervalIndex<T> {
+    intervals: Vec<Interval<T>>,
+    max_level: usize,
+}
+
+impl<T> Default for IntervalIndex<T> {
+    fn default() -> Self {
+        IntervalIndex {
+            intervals: Vec::new(),
+            max_level: 0,
+        }
+    }
+}
+
+impl<T> IntervalIndex<T> {
+    pub fn new(intervals: Vec<(usize, usize, T)>) -> Self {
+        let mut intervals = intervals
+            .into_iter()
+            .map(|(start, end, value)| Interval {
+                start,
+                end,
+                max: end,
+                value,
+            })
+            .collect::<Vec<Interval<T>>>();
+
+        intervals.sort_by_key(|x| (x.start, x.end));
+
+        let max_level = index(&mut intervals);
+        IntervalIndex {
+            intervals,
+            max_level,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.intervals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Everything, sorted by start
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.intervals.iter().map(|x| &x.value)
+    }
+
+    /// Values overlapping [start, end), sorted by start
+    pub fn overlapping(&self, start: usize, end: usize) -> Vec<&T> {
+        self.overlapping_positions(start, end)
+            .into_iter()
+            .map(|n| &self.intervals[n].value)
+            .collect()
+    }
+
+    /// Same, as positions for get. They stay the same for the life of the index, so they can be
+    /// used to keep track of what has been done with each value.
+    pub fn overlapping_positions(&self, start: usize, end: usize) -> Vec<usize> {
+        let mut found = self.overlapping_indices(start, end);
+        found.sort_unstable();
+        found
+    }
+
+    pub fn get(&self, position: usize) -> Option<&T> {
+        self.intervals.get(position).map(|x| &x.value)
+    }
+
+    /// Greedy row packing by start: each interval goes in the first row whose last interval ends
+    /// at least gap before it. Rows by position, and how many there are.
+    pub fn pack_rows(&self, gap: usize) -> (Vec<usize>, usize) {
+        let mut row_ends: Vec<usize> = Vec::new();
+        let rows = self
+            .intervals
+            .iter()
+            .map(
+                |x| match row_ends.iter().position(|end| end + gap <= x.start) {
+                    Some(row) => {
+                        row_ends[row] = x.end;
+                        row
+                    }
+                    None => {
+                        row_ends.push(x.end);
+                        row_ends.len() - 1
+                    }
+                },
+            )
+            .collect();
+
+        (rows, row_ends.len())
+    }
+
+    pub fn count_overlapping(&self, start: usize, end: usize) -> usize {
+        self.overlapping_indices(start, end).len()
+    }
+
+    fn overlapping_indices(&self, start: usize, end: usize) -> Vec<usize> {
+        let a = &self.intervals;
+        let n = a.len();
+        let mut found = Vec::new();
+
+        if n == 0 || start >= end {
+            return found;
+        }
+
+        // (node, level, left subtree already visited)
+        let mut stack = vec![((1 << self.max_level) - 1, self.max_level, false)];
+
+        while let Some((x, k, visited)) = stack.pop() {
+            if k <= 3 {
+                // Small subtree, scan it
+                let i0 = x >> k << k;
+                let i1 = std::cmp::min(i0 + (1 << (k + 1)) - 1, n);
+                for (i, interval) in a.iter().enumerate().take(i1).skip(i0) {
+                    if interval.start >= end {
+                        break;
+                    }
+                    if start < interval.end {
+                        found.push(i);
+                    }
+                }
+            } else if !visited {
+                let left = x - (1 << (k - 1));
+                stack.push((x, k, true));
+                // Nodes past the end only exist to keep the tree complete, always go left of them
+                if left >= n || a[left].max > start {
+                    stack.push((left, k - 1, false));
+                }
+            } else if x < n && a[x].start < end {
+                if start < a[x].end {
+                    found.push(x);
+                }
+                stack.push((x + (1 << (k - 1)), k - 1, false));
+            }
+        }
+
+        found
+    }
+}
+
+// Fills in max for every node, returns the level of the root
+fn index<T>(a: &mut [Interval<T>]) -> usize {
+    let n = a.len();
+    if n == 0 {
+        return 0;
+    }
+
+    // Leaves are the even indices
+    let mut last_i = 0;
+    let mut last = 0;
+    for i in (0..n).step_by(2) {
+        last_i = i;
+        last = a[i].end;
+        a[i].max = last;
+    }
+
+    let mut k = 1;
+    while 1 << k <= n {
+        let x = 1 << (k - 1);
+        let step = x << 2;
+
+        let mut i = (x << 1) - 1;
+        while i < n {
+            let left = a[i - x].max;
+            let right = if i + x < n { a[i + x].max } else { last };
+            a[i].max = a[i].end.max(left).max(right);
+            i += step;
+        }
+
+        // The last node at this level, its parent may be past the end
+        last_i = if (last_i >> k) & 1 == 1 {
+            last_i - x
+        } else {
+            last_i + x
+        };
+        if last_i < n && a[last_i].max > last {
+            last = a[last_i].max;
+        }
+
+        k += 1;
+    }
+
+    k - 1
+}
+
+impl IntervalIndex<Feature> {
+    /// GFF coordinates are 1-based and inclusive, they are converted here
+    pub fn from_features(features: Vec<Feature>) -> Self {
+        IntervalIndex::new(
+            features
+                .into_iter()
+                .map(|x| (x.start.saturating_sub(1), x.end.max(x.start), x))
+                .collect(),
+        )
+    }
+
+    /// Packs the features into rows, see pack_rows, and stores each one's in its y_offset.
+    /// Returns the number of rows.
+    pub fn pack_features(&mut self, gap: usize) -> usize {
+        let (rows, count) = self.pack_rows(gap);
+        for (interval, row) in self.intervals.iter_mut().zip(rows) {
+            interval.value.y_offset = row;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlapping() {
+        // Nested, disjoint and long intervals, enough of them to get past the linear scans
+        let mut intervals = Vec::new();
+        for i in 0..200usize {
+            intervals.push((i * 10, i * 10 + 5, i));
+        }
+        intervals.push((15, 1500, 1000));
+        intervals.push((700, 701, 1001));
+
+        let index = IntervalIndex::new(intervals.clone());
+        assert_eq!(index.len(), 202);
+
+        for (start, end) in [
+            (0, 1),
+            (3, 12),
+            (699, 702),
+            (1499, 1600),
+            (5, 10),
+            (0, 3000),
+        ] {
+            let mut expected = intervals
+                .iter()
+                .filter(|x| x.0 < end && start < x.1)
+                .map(|x| x.2)
+                .collect::<Vec<usize>>();
+            let mut found = index
+                .overlapping(start, end)
+                .into_iter()
+                .copied()
+                .collect::<Vec<usize>>();
+
+            expected.sort_unstable();
+            found.sort_unstable();
+            assert_eq!(found, expected, "{}-{}", start, end);
+        }
+
+        assert_eq!(index.count_overlapping(5, 10), 0);
+        for n in index.overlapping_positions(695, 705) {
+            assert!([69, 70, 1000, 1001].contains(index.get(n).unwrap()));
+        }
+        assert!(IntervalIndex::<usize>::default()
+            .overlapping(0, 10)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_from_features() {
+        let feature = Feature {
+            start: 11,
+            end: 20,
+            ..Default::default()
+        };
+        let index = IntervalIndex::from_features(vec![feature]);
+
+        assert_eq!(index.overlapping(10, 11).len(), 1);
+        assert_eq!(index.overlapping(19, 30).len(), 1);
+        assert!(index.overlapping(0, 10).is_empty());
+        assert!(index.overlapping(20, 30).is_empty());
+    }
+
+    #[test]
+    fn test_pack_rows() {
+        let index = IntervalIndex::new(vec![
+            (0, 100, 'a'),
+            (50, 150, 'b'),
+            (100, 200, 'c'), // Fits after a
+            (105, 120, 'd'), // Too close to a with a gap of 10, b is still going
+            (160, 170, 'e'), // After b, or a when c moved out of its row
+        ]);
+
+        let (rows, count) = index.pack_rows(0);
+        assert_eq!(rows, vec![0, 1, 0, 2, 1]);
+        assert_eq!(count, 3);
+
+        let (rows, count) = index.pack_rows(10);
+        assert_eq!(rows, vec![0, 1, 2, 3, 0]);
+        assert_eq!(count, 4);
+
+        let mut features = IntervalIndex::from_features(vec![
+            Feature {
+                start: 1,
+                end: 10,
+                ..Default::default()
+            },
+            Feature {
+                start: 5,
+                end: 20,
+                ..Default::default()
+            },
+        ]);
+        assert_eq!(features.pack_features(0), 2);
+        assert_eq!(
+            features.iter().map(|x| x.y_offset).collect::<Vec<usize>>(),
+            vec![0, 1]
+        );
+        assert_eq!(IntervalIndex::<usize>::default().pack_rows(0).1, 0);
+    }
+}
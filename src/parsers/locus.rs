@@ -0,0 +1,153 @@
+use std::fmt;
+use std::str::FromStr;
+
+// What can be typed in the locus box: chr1, chr1:5,000 or chr1:10,000-20,000. Coordinates are
+// 1-based and inclusive, like samtools regions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Locus {
+    pub landmark: String,
+    pub start: Option<usize>,
+    pub end: Option<usize>, // Only with start
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidLocus {
+    Empty,
+    InvalidPosition(String),
+    EmptyRange { start: usize, end: usize },
+}
+
+impl fmt::Display for InvalidLocus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidLocus::Empty => write!(f, "nothing to go to"),
+            InvalidLocus::InvalidPosition(x) => write!(f, "invalid position {}", x),
+            InvalidLocus::EmptyRange { start, end } => {
+                write!(f, "end {} is before start {}", end, start)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidLocus {}
+
+impl Locus {
+    /// 0-based, half-open range, clamped to length. Bare landmarks are the whole of it, single
+    /// positions are centered in a window of context bases.
+    pub fn range(&self, length: usize, context: usize) -> (usize, usize) {
+        let (start, end) = match (self.start, self.end) {
+            (Some(start), Some(end)) => (start - 1, end),
+            (Some(position), None) => {
+                let position = position - 1;
+                (
+                    position.saturating_sub(context / 2),
+                    position + context / 2 + 1,
+                )
+            }
+            _ => (0, length),
+        };
+
+        let end = std::cmp::min(end, length);
+        (std::cmp::min(start, end), end)
+    }
+}
+
+fn position(x: &str) -> Result<usize, InvalidLocus> {
+    let digits = x.trim().replace(',', "");
+    match digits.parse::<usize>() {
+        Ok(0) | Err(_) => Err(InvalidLocus::InvalidPosition(x.trim().to_string())),
+        Ok(x) => Ok(x),
+    }
+}
+
+impl FromStr for Locus {
+    type Err = InvalidLocus;
+
+    // Landmark names may have colons of their own (HLA-A*01:01), the last one only starts
+    // coordinates if what follows looks like them. A name that is all digits after its last
+    // colon can't be told apart from a position, callers look the whole string up first.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(InvalidLocus::Empty);
+        }
+
+        let (landmark, coordinates) = match s.rsplit_once(':') {
+            Some((landmark, coordinates))
+                if !landmark.is_empty()
+                    && coordinates
+                        .chars()
+                        .all(|c| c.is_ascii_digit() || c == ',' || c == '-' || c == ' ') =>
+            {
+                (landmark, coordinates)
+            }
+            _ => {
+                return Ok(Locus {
+                    landmark: s.to_string(),
+                    start: None,
+                    end: None,
+                })
+            }
+        };
+
+        let (start, end) = match coordinates.split_once('-') {
+            Some((start, end)) => (position(start)?, Some(position(end)?)),
+            None => (position(coordinates)?, None),
+        };
+
+        if let Some(end) = end {
+            if end < start {
+                return Err(InvalidLocus::EmptyRange { start, end });
+            }
+        }
+
+        Ok(Locus {
+            landmark: landmark.trim().to_string(),
+            start: Some(start),
+            end,
+        })
+    }
+}
+
+impl fmt::Display for Locus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => write!(f, "{}:{}-{}", self.landmark, start, end),
+            (Some(start), None) => write!(f, "{}:{}", self.landmark, start),
+            _ => write!(f, "{}", self.landmark),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_locus() {
+        let locus = "chr1:10,000-20,000".parse::<Locus>().unwrap();
+        assert_eq!(locus.landmark, "chr1");
+        assert_eq!((locus.start, locus.end), (Some(10000), Some(20000)));
+        assert_eq!(locus.range(15000, 100), (9999, 15000));
+        assert_eq!(locus.to_string(), "chr1:10000-20000");
+
+        let locus = " chr2:500 ".parse::<Locus>().unwrap();
+        assert_eq!((locus.start, locus.end), (Some(500), None));
+        assert_eq!(locus.range(10000, 100), (449, 550));
+
+        let locus = "HLA-A*01:01:5-10".parse::<Locus>().unwrap();
+        assert_eq!(locus.landmark, "HLA-A*01:01");
+
+        let locus = "scaffold_1".parse::<Locus>().unwrap();
+        assert_eq!(locus.start, None);
+        assert_eq!(locus.range(3000, 100), (0, 3000));
+
+        assert_eq!("".parse::<Locus>(), Err(InvalidLocus::Empty));
+        assert_eq!(
+            "chr1:20-10".parse::<Locus>(),
+            Err(InvalidLocus::EmptyRange { start: 20, end: 10 })
+        );
+        assert!("chr1:0-10".parse::<Locus>().is_err());
+        assert!("chr1:-10".parse::<Locus>().is_err());
+    }
+}
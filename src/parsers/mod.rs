@@ -1,9 +1,43 @@
+pub mod attributes;
+pub mod bed;
+pub mod bgzf;
+pub mod cigar;
+pub mod error;
+pub mod fasta;
 pub mod feature;
 pub mod gfa;
 pub mod gff3;
+pub mod gtf;
+pub mod hierarchy;
+pub mod interval;
+pub mod locus;
 pub mod plugin;
+pub mod progress;
+pub mod search;
+pub mod sequence;
+pub mod sniff;
+pub mod tabix;
+pub mod transcript;
+pub mod vcf;
 
+pub use attributes::*;
+pub use bed::*;
+pub use bgzf::*;
+pub use cigar::*;
+pub use error::*;
+pub use fasta::*;
 pub use feature::*;
 pub use gfa::*;
 pub use gff3::*;
+pub use gtf::*;
+pub use hierarchy::*;
+pub use interval::*;
+pub use locus::*;
 pub use plugin::*;
+pub use progress::*;
+pub use search::*;
+pub use sequence::*;
+pub use sniff::*;
+pub use tabix::*;
+pub use transcript::*;
+pub use vcf::*;
@@ -0,0 +1,82 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use super::bgzf::InputFile;
+
+// How often, in lines, parsers report where they are
+pub(crate) const PROGRESS_LINES: usize = 16384;
+
+/// Bytes read out of the file's size, shared between a parser running on another thread and
+/// whatever shows how far along it is. Compressed files count compressed bytes.
+#[derive(Clone, Debug, Default)]
+pub struct Progress {
+    read: Arc<AtomicU64>,
+    total: Arc<AtomicU64>,
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Progress::default()
+    }
+
+    pub fn start(&self, filename: &str) {
+        let total = std::fs::metadata(filename).map(|x| x.len()).unwrap_or(0);
+        self.total.store(total, Ordering::Relaxed);
+        self.read.store(0, Ordering::Relaxed);
+    }
+
+    pub fn update(&self, file: &InputFile) {
+        self.read
+            .store(file.compressed_position(), Ordering::Relaxed);
+    }
+
+    pub fn finish(&self) {
+        self.read
+            .store(self.total.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    pub fn read(&self) -> u64 {
+        self.read.load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// 0 to 1, 0 until the size is known
+    pub fn fraction(&self) -> f32 {
+        match self.total() {
+            0 => 0.0,
+            total => (self.read() as f64 / total as f64).min(1.0) as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress() {
+        let progress = Progress::new();
+        let shared = progress.clone();
+        assert_eq!(progress.fraction(), 0.0);
+
+        shared.start("test_data/test.gfa");
+        let total = std::fs::metadata("test_data/test.gfa").unwrap().len();
+        assert_eq!(progress.total(), total);
+
+        let mut file = InputFile::open("test_data/test.gfa").unwrap();
+        std::io::copy(&mut file, &mut std::io::sink()).unwrap();
+        shared.update(&file);
+        assert_eq!(progress.fraction(), 1.0);
+
+        // Compressed input counts compressed bytes
+        shared.start("test_data/test.gtf.gz");
+        let file = InputFile::open("test_data/test.gtf.gz").unwrap();
+        shared.update(&file);
+        assert!(progress.read() <= progress.total());
+        shared.finish();
+        assert_eq!(progress.read(), progress.total());
+    }
+}
@@ -0,0 +1,204 @@
+use super::feature::Feature;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchEntry {
+    pub name: String,         // What matched, as written in the file
+    pub feature_type: String, // "segment" for GFA segments
+    pub landmark: String,
+    pub start: usize, // 0-based, half-open
+    pub end: usize,
+}
+
+/// Feature Name, ID and Alias attributes and GFA segment IDs. Prefix matches come first, then
+/// fuzzy ones (the query's characters in order, gaps allowed), all case-insensitive.
+#[derive(Clone, Debug, Default)]
+pub struct SearchIndex {
+    entries: Vec<SearchEntry>,
+    keys: Vec<(String, usize)>, // Lower case, sorted, into entries
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        SearchIndex::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds features and their subfeatures, call finish once everything is in
+    pub fn add_features(&mut self, features: &[Feature]) {
+        for feature in features {
+            let attributes = &feature.attributes;
+            let names = attributes
+                .name
+                .iter()
+                .chain(attributes.id.iter())
+                .chain(attributes.alias.iter());
+
+            for name in names {
+                self.add(SearchEntry {
+                    name: name.clone(),
+                    feature_type: feature.feature_type.clone(),
+                    landmark: feature.landmark.clone(),
+                    start: feature.start.saturating_sub(1),
+                    end: feature.end,
+                });
+            }
+
+            self.add_features(feature.children());
+        }
+    }
+
+    pub fn add_segment(&mut self, id: &str, length: usize) {
+        self.add(SearchEntry {
+            name: id.to_string(),
+            feature_type: "segment".to_string(),
+            landmark: id.to_string(),
+            start: 0,
+            end: length,
+        });
+    }
+
+    fn add(&mut self, entry: SearchEntry) {
+        self.keys
+            .push((entry.name.to_lowercase(), self.entries.len()));
+        self.entries.push(entry);
+    }
+
+    pub fn finish(&mut self) {
+        self.keys.sort_unstable();
+        self.keys
+            .dedup_by(|a, b| a.0 == b.0 && self.entries[a.1] == self.entries[b.1]);
+    }
+
+    /// Adds another finished index, so files can be indexed one at a time as they load
+    pub fn merge(&mut self, other: SearchIndex) {
+        let offset = self.entries.len();
+        self.keys
+            .extend(other.keys.into_iter().map(|(key, n)| (key, n + offset)));
+        self.entries.extend(other.entries);
+        self.finish();
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> Vec<&SearchEntry> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() || limit == 0 {
+            return Vec::new();
+        }
+
+        let first = self.keys.partition_point(|x| x.0.as_str() < query.as_str());
+        let mut found = self.keys[first..]
+            .iter()
+            .take_while(|x| x.0.starts_with(&query))
+            .take(limit)
+            .map(|x| x.1)
+            .collect::<Vec<usize>>();
+
+        if found.len() < limit {
+            let mut fuzzy = self
+                .keys
+                .iter()
+                .filter(|x| !x.0.starts_with(&query))
+                .filter_map(|x| fuzzy_score(&x.0, &query).map(|score| (score, x.1)))
+                .collect::<Vec<(usize, usize)>>();
+            fuzzy.sort_unstable();
+            found.extend(fuzzy.into_iter().take(limit - found.len()).map(|x| x.1));
+        }
+
+        found.into_iter().map(|n| &self.entries[n]).collect()
+    }
+}
+
+// Characters skipped between the first and last matched, lower is better. None if the query isn't
+// a subsequence of key.
+fn fuzzy_score(key: &str, query: &str) -> Option<usize> {
+    let mut query = query.chars().peekable();
+    let mut first = None;
+    let mut gaps = 0;
+
+    for (n, c) in key.chars().enumerate() {
+        match query.peek() {
+            Some(&x) if x == c => {
+                first.get_or_insert(n);
+                query.next();
+            }
+            Some(_) if first.is_some() => gaps += 1,
+            Some(_) => (),
+            None => break,
+        }
+    }
+
+    match query.peek() {
+        None => Some(gaps),
+        Some(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::attributes::Attributes;
+
+    #[test]
+    fn test_search() {
+        let transcript = Feature {
+            landmark: "chr1".to_string(),
+            feature_type: "mRNA".to_string(),
+            start: 101,
+            end: 200,
+            attributes: Attributes {
+                id: Some("BRCA2-201".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let gene = Feature {
+            landmark: "chr1".to_string(),
+            feature_type: "gene".to_string(),
+            start: 101,
+            end: 500,
+            attributes: Attributes {
+                id: Some("gene:ENSG00000139618".to_string()),
+                name: Some("BRCA2".to_string()),
+                alias: vec!["FANCD1".to_string()],
+                ..Default::default()
+            },
+            subfeatures: Some(vec![transcript]),
+            ..Default::default()
+        };
+
+        let mut index = SearchIndex::new();
+        index.add_features(&[gene]);
+        index.add_segment("s1", 1000);
+        index.finish();
+        assert_eq!(index.len(), 5);
+
+        let found = index.search("brca", 10);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].name, "BRCA2");
+        assert_eq!((found[0].start, found[0].end), (100, 500));
+        assert_eq!(found[1].feature_type, "mRNA");
+
+        assert_eq!(index.search("fancd1", 10)[0].landmark, "chr1");
+        assert_eq!(index.search("S1", 10)[0].feature_type, "segment");
+
+        // Fuzzy, after the prefix matches
+        let found = index.search("bc2", 10);
+        assert_eq!(found[0].name, "BRCA2");
+        assert!(index.search("brca", 1).len() == 1);
+        assert!(index.search("zzz", 10).is_empty());
+
+        let mut other = SearchIndex::new();
+        other.add_segment("s2", 500);
+        other.finish();
+        index.merge(other);
+        assert_eq!(index.len(), 6);
+        assert_eq!(index.search("s2", 10)[0].end, 500);
+        assert_eq!(index.search("brca2", 1)[0].name, "BRCA2");
+    }
+}
@@ -0,0 +1,274 @@
+use twox_hash::RandomXxh3HashBuilder64;
+
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use super::bgzf::*;
+use super::error::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SequenceStorage {
+    Discard, // Only lengths are kept
+    Packed,  // 2 bits per base in memory
+    OnDisk,  // Only where to find it, read back from the file when asked for
+}
+
+impl Default for SequenceStorage {
+    fn default() -> Self {
+        SequenceStorage::Packed
+    }
+}
+
+// A run of a base that doesn't fit in 2 bits (N, IUPAC codes), relative to the sequence start
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Exception {
+    start: usize,
+    length: usize,
+    base: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StoredSequence {
+    Packed {
+        start: usize, // In bases, into packed
+        length: usize,
+        exceptions: (usize, usize), // Range into exceptions
+    },
+    OnDisk {
+        position: u64, // Of the line, as given by InputFile::position
+        column: usize, // Byte offset of the sequence in the line
+        length: usize,
+    },
+}
+
+impl StoredSequence {
+    fn length(&self) -> usize {
+        match self {
+            StoredSequence::Packed { length, .. } => *length,
+            StoredSequence::OnDisk { length, .. } => *length,
+        }
+    }
+}
+
+/// Segment sequences, by ID. Packed storage folds everything to upper case, runs of anything
+/// other than ACGT are kept on the side like the N blocks of UCSC's 2bit.
+#[derive(Clone, Debug, Default)]
+pub struct SequenceStore {
+    pub storage: SequenceStorage,
+    pub filename: String, // Read from for OnDisk storage
+    packed: Vec<u8>,      // 4 bases per byte
+    packed_length: usize, // In bases
+    exceptions: Vec<Exception>,
+    sequences: HashMap<String, StoredSequence, RandomXxh3HashBuilder64>,
+}
+
+impl SequenceStore {
+    pub fn new(storage: SequenceStorage, filename: &str) -> Self {
+        SequenceStore {
+            storage,
+            filename: filename.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.sequences.contains_key(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sequences.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sequences.is_empty()
+    }
+
+    pub fn length(&self, id: &str) -> Option<usize> {
+        self.sequences.get(id).map(|x| x.length())
+    }
+
+    /// Bytes used by the packed bases, for the curious
+    pub fn packed_bytes(&self) -> usize {
+        self.packed.len() + self.exceptions.len() * std::mem::size_of::<Exception>()
+    }
+
+    pub fn insert(&mut self, id: &str, sequence: &[u8]) {
+        if self.storage != SequenceStorage::Packed {
+            return;
+        }
+
+        let start = self.packed_length;
+        let first_exception = self.exceptions.len();
+
+        self.packed
+            .reserve((start + sequence.len() + 3) / 4 - self.packed.len());
+
+        for (n, base) in sequence.iter().map(|x| x.to_ascii_uppercase()).enumerate() {
+            let code = match base {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => {
+                    match self.exceptions[first_exception..].last_mut() {
+                        Some(x) if x.base == base && x.start + x.length == n => x.length += 1,
+                        _ => self.exceptions.push(Exception {
+                            start: n,
+                            length: 1,
+                            base,
+                        }),
+                    }
+                    0
+                }
+            };
+
+            let position = start + n;
+            if position % 4 == 0 {
+                self.packed.push(0);
+            }
+            *self.packed.last_mut().unwrap() |= code << ((position % 4) * 2);
+        }
+
+        self.packed_length += sequence.len();
+        self.sequences.insert(
+            id.to_string(),
+            StoredSequence::Packed {
+                start,
+                length: sequence.len(),
+                exceptions: (first_exception, self.exceptions.len()),
+            },
+        );
+    }
+
+    pub fn insert_on_disk(&mut self, id: &str, position: u64, column: usize, length: usize) {
+        if self.storage != SequenceStorage::OnDisk {
+            return;
+        }
+
+        self.sequences.insert(
+            id.to_string(),
+            StoredSequence::OnDisk {
+                position,
+                column,
+                length,
+            },
+        );
+    }
+
+    pub fn get(&self, id: &str) -> Result<Vec<u8>, ParseError> {
+        self.fetch(id, 0, usize::MAX)
+    }
+
+    /// Bases of a sequence in [start, end), 0-based. The end is clamped to the sequence length.
+    pub fn fetch(&self, id: &str, start: usize, end: usize) -> Result<Vec<u8>, ParseError> {
+        let stored = match self.sequences.get(id) {
+            Some(x) => x,
+            None => {
+                return Err(ParseError::new(ParseErrorKind::UnknownSequence(
+                    id.to_string(),
+                )))
+            }
+        };
+
+        let end = std::cmp::min(end, stored.length());
+        if start >= end {
+            return Ok(Vec::new());
+        }
+
+        match *stored {
+            StoredSequence::Packed {
+                start: offset,
+                exceptions,
+                ..
+            } => {
+                let mut bases = (offset + start..offset + end)
+                    .map(|x| b"ACGT"[((self.packed[x / 4] >> ((x % 4) * 2)) & 3) as usize])
+                    .collect::<Vec<u8>>();
+
+                for exception in self.exceptions[exceptions.0..exceptions.1].iter() {
+                    let from = std::cmp::max(exception.start, start);
+                    let to = std::cmp::min(exception.start + exception.length, end);
+                    for x in from..to {
+                        bases[x - start] = exception.base;
+                    }
+                }
+
+                Ok(bases)
+            }
+            StoredSequence::OnDisk {
+                position, column, ..
+            } => {
+                let mut line = Vec::new();
+                let read = InputFile::open(&self.filename).and_then(|mut file| {
+                    file.seek_to(position)?;
+                    file.read_until(b'\n', &mut line)
+                });
+                if let Err(err) = read {
+                    return Err(ParseError::from(err).with_file(&self.filename));
+                }
+
+                match line.get(column + start..column + end) {
+                    Some(x) => Ok(x.to_vec()),
+                    None => Err(ParseError::new(ParseErrorKind::LengthMismatch {
+                        expected: end,
+                        found: line.len().saturating_sub(column),
+                    })
+                    .with_file(&self.filename)),
+                }
+            }
+        }
+    }
+}
+
+pub fn complement(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' | b'U' => b'A',
+        b'a' => b't',
+        b'c' => b'g',
+        b'g' => b'c',
+        b't' | b'u' => b'a',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        x => x, // N, S, W and anything else
+    }
+}
+
+pub fn reverse_complement(sequence: &[u8]) -> Vec<u8> {
+    sequence.iter().rev().map(|&x| complement(x)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packed() {
+        let mut store = SequenceStore::new(SequenceStorage::Packed, "");
+        store.insert("1", b"ACGTNNNNacgtRA");
+        store.insert("2", b"TTTGA");
+
+        assert_eq!(store.get("1").unwrap(), b"ACGTNNNNACGTRA");
+        assert_eq!(store.get("2").unwrap(), b"TTTGA");
+        assert_eq!(store.fetch("1", 6, 13).unwrap(), b"NNACGTR");
+        assert_eq!(store.length("1"), Some(14));
+        assert_eq!(
+            store.packed_bytes(),
+            5 + 2 * std::mem::size_of::<Exception>()
+        );
+        assert!(store.get("3").is_err());
+    }
+
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(reverse_complement(b"ACGTNacR"), b"YgtNACGT");
+    }
+}
@@ -0,0 +1,155 @@
+use std::io::BufRead;
+
+use super::bgzf::*;
+use super::error::*;
+
+// Lines looked at past any comments, more only helps with odd headers
+const SNIFF_LINES: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Gfa,
+    Gff3,
+    Gtf,
+    Fasta,
+    Bed,
+    Vcf,
+}
+
+impl Format {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Gfa => "GFA",
+            Format::Gff3 => "GFF3",
+            Format::Gtf => "GTF",
+            Format::Fasta => "FASTA",
+            Format::Bed => "BED",
+            Format::Vcf => "VCF",
+        }
+    }
+
+    /// From the extension, .gz and .bgz are looked past
+    pub fn from_extension(filename: &str) -> Option<Self> {
+        let filename = filename.to_ascii_lowercase();
+        let filename = filename
+            .strip_suffix(".gz")
+            .or_else(|| filename.strip_suffix(".bgz"))
+            .unwrap_or(&filename);
+
+        match filename.rsplit_once('.')?.1 {
+            "gfa" | "gfa1" | "gfa2" => Some(Format::Gfa),
+            "gff3" | "gff" => Some(Format::Gff3),
+            "gtf" => Some(Format::Gtf),
+            "fa" | "fasta" | "fna" | "fas" => Some(Format::Fasta),
+            "bed" | "bedgraph" => Some(Format::Bed),
+            "vcf" => Some(Format::Vcf),
+            _ => None,
+        }
+    }
+
+    /// From the first lines of a file, header lines are the surest sign
+    pub fn from_lines<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        for line in lines {
+            let line = line.trim_end();
+
+            if line.starts_with("##gff-version") {
+                return Some(Format::Gff3);
+            } else if line.starts_with("##fileformat=VCF") {
+                return Some(Format::Vcf);
+            } else if line.starts_with('>') {
+                return Some(Format::Fasta);
+            } else if line.starts_with("track") || line.starts_with("browser") {
+                return Some(Format::Bed);
+            } else if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.split('\t').collect::<Vec<&str>>();
+            let integer = |n: usize| fields.get(n).map_or(false, |x| x.parse::<u64>().is_ok());
+
+            return match fields[0] {
+                "H" | "S" | "L" | "P" | "W" | "C" | "E" | "F" | "G" | "O" | "U"
+                    if fields.len() > 1 =>
+                {
+                    Some(Format::Gfa)
+                }
+                _ if fields.len() == 9 && integer(3) && integer(4) => {
+                    if fields[8].contains("gene_id \"") {
+                        Some(Format::Gtf)
+                    } else {
+                        Some(Format::Gff3)
+                    }
+                }
+                _ if fields.len() >= 3 && integer(1) && integer(2) => Some(Format::Bed),
+                _ => None,
+            };
+        }
+
+        None
+    }
+
+    /// Looks inside first, plain or compressed, then falls back to the extension
+    pub fn sniff(filename: &str) -> Result<Self, ParseError> {
+        let mut file =
+            InputFile::open(filename).map_err(|x| ParseError::from(x).with_file(filename))?;
+
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        while lines.len() < SNIFF_LINES {
+            line.clear();
+            // Binary files aren't anything we read, the extension can have a go
+            match file.read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => lines.push(line.clone()),
+            }
+        }
+
+        Format::from_lines(lines.iter().map(|x| x.as_str()))
+            .or_else(|| Format::from_extension(filename))
+            .ok_or_else(|| ParseError::new(ParseErrorKind::UnknownFormat).with_file(filename))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_format() {
+        assert_eq!(
+            Format::from_lines(["H\tVN:Z:1.0", "S\ts1\tACGT"]),
+            Some(Format::Gfa)
+        );
+        assert_eq!(
+            Format::from_lines(["##gff-version 3", "chr1\t.\tgene\t1\t10\t.\t+\t.\tID=a"]),
+            Some(Format::Gff3)
+        );
+        assert_eq!(
+            Format::from_lines([
+                "#!genome-build x",
+                "chr1\tsrc\tgene\t1\t10\t.\t+\t.\tgene_id \"a\";"
+            ]),
+            Some(Format::Gtf)
+        );
+        assert_eq!(
+            Format::from_lines([">chr1 description", "ACGT"]),
+            Some(Format::Fasta)
+        );
+        assert_eq!(
+            Format::from_lines(["##fileformat=VCFv4.2", "#CHROM\tPOS"]),
+            Some(Format::Vcf)
+        );
+        assert_eq!(
+            Format::from_lines(["chr1\t100\t200\tpeak1"]),
+            Some(Format::Bed)
+        );
+        assert_eq!(Format::from_lines(["", "no idea"]), None);
+
+        assert_eq!(Format::from_extension("calls.vcf.gz"), Some(Format::Vcf));
+        assert_eq!(Format::from_extension("Genome.FA"), Some(Format::Fasta));
+        assert_eq!(Format::from_extension("notes.txt"), None);
+
+        assert_eq!(Format::sniff("test_data/test.gfa").unwrap(), Format::Gfa);
+        assert!(Format::sniff("test_data/missing.gfa").is_err());
+    }
+}
@@ -0,0 +1,432 @@
+use twox_hash::RandomXxh3HashBuilder64;
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use super::bgzf::*;
+use super::error::*;
+
+// Holds the reference's offsets and record counts instead of chunks
+const PSEUDO_BIN: u32 = 37450;
+
+// Tabix's fixed binning scheme, CSI makes both configurable
+const TBI_MIN_SHIFT: u32 = 14;
+const TBI_DEPTH: u32 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    pub start: VirtualOffset,
+    pub end: VirtualOffset,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Bin {
+    pub loffset: VirtualOffset, // CSI only, no record overlapping the bin's start comes before it
+    pub chunks: Vec<Chunk>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ReferenceIndex {
+    pub bins: HashMap<u32, Bin, RandomXxh3HashBuilder64>,
+    pub linear: Vec<VirtualOffset>, // TBI only, smallest offset per 16kbp window
+    pub start: Option<VirtualOffset>,
+    pub end: Option<VirtualOffset>,
+    pub mapped: Option<u64>, // Number of records, when the pseudo-bin is present
+}
+
+/// A .tbi or .csi index of a bgzipped, position sorted file
+#[derive(Clone, Debug)]
+pub struct TabixIndex {
+    pub format: i32,
+    pub col_seq: usize, // 1-based, as in the index
+    pub col_beg: usize,
+    pub col_end: usize,
+    pub meta: u8, // Lines starting with this are skipped
+    pub skip: usize,
+    pub names: Vec<String>,
+    pub references: Vec<ReferenceIndex>,
+    pub min_shift: u32,
+    pub depth: u32,
+}
+
+impl TabixIndex {
+    /// The index next to a data file, filename.tbi or filename.csi, if there is one
+    pub fn find(filename: &str) -> Option<String> {
+        ["tbi", "csi"]
+            .iter()
+            .map(|x| format!("{}.{}", filename, x))
+            .find(|x| std::path::Path::new(x).exists())
+    }
+
+    pub fn read(filename: &str) -> Result<TabixIndex, ParseError> {
+        let mut bytes = Vec::new();
+        let read = InputFile::open(filename).and_then(|mut x| x.read_to_end(&mut bytes));
+        if let Err(err) = read {
+            return Err(ParseError::from(err).with_file(filename));
+        }
+
+        let mut reader = Bytes {
+            bytes: &bytes,
+            position: 0,
+        };
+
+        let index = match reader.take(4) {
+            Some(b"TBI\x01") => TabixIndex::read_tbi(&mut reader),
+            Some(b"CSI\x01") => TabixIndex::read_csi(&mut reader),
+            _ => None,
+        };
+
+        index.ok_or_else(|| {
+            ParseError::new(ParseErrorKind::InvalidIndex(format!(
+                "malformed tabix index at byte {}",
+                reader.position
+            )))
+            .with_file(filename)
+        })
+    }
+
+    fn read_tbi(reader: &mut Bytes) -> Option<TabixIndex> {
+        let n_ref = reader.count()?;
+        let mut index = TabixIndex::read_header(reader, TBI_MIN_SHIFT, TBI_DEPTH)?;
+
+        for _ in 0..n_ref {
+            let mut reference = ReferenceIndex::default();
+
+            for _ in 0..reader.count()? {
+                let bin_number = reader.u32()?;
+                let chunks = reader.chunks()?;
+                reference.add_bin(bin_number, Bin::default(), chunks);
+            }
+
+            for _ in 0..reader.count()? {
+                reference.linear.push(VirtualOffset(reader.u64()?));
+            }
+
+            index.references.push(reference);
+        }
+
+        Some(index)
+    }
+
+    fn read_csi(reader: &mut Bytes) -> Option<TabixIndex> {
+        let min_shift = reader.i32()? as u32;
+        let depth = reader.i32()? as u32;
+
+        // Tabix's header is stored as CSI's auxiliary data. Without it (a CSI for BAM/BCF) the
+        // names would have to come from the data file's header, which isn't supported
+        let l_aux = reader.count()?;
+        let mut aux = Bytes {
+            bytes: reader.take(l_aux)?,
+            position: 0,
+        };
+        let mut index = TabixIndex::read_header(&mut aux, min_shift, depth)?;
+
+        let n_ref = reader.count()?;
+        for _ in 0..n_ref {
+            let mut reference = ReferenceIndex::default();
+
+            for _ in 0..reader.count()? {
+                let bin_number = reader.u32()?;
+                let bin = Bin {
+                    loffset: VirtualOffset(reader.u64()?),
+                    chunks: Vec::new(),
+                };
+                let chunks = reader.chunks()?;
+                reference.add_bin(bin_number, bin, chunks);
+            }
+
+            index.references.push(reference);
+        }
+
+        Some(index)
+    }
+
+    fn read_header(reader: &mut Bytes, min_shift: u32, depth: u32) -> Option<TabixIndex> {
+        let format = reader.i32()?;
+        let col_seq = reader.i32()? as usize;
+        let col_beg = reader.i32()? as usize;
+        let col_end = reader.i32()? as usize;
+        let meta = reader.i32()? as u8;
+        let skip = reader.i32()? as usize;
+        let l_nm = reader.count()?;
+
+        let names = reader
+            .take(l_nm)?
+            .split(|&x| x == 0)
+            .filter(|x| !x.is_empty())
+            .map(|x| String::from_utf8_lossy(x).into_owned())
+            .collect();
+
+        Some(TabixIndex {
+            format,
+            col_seq,
+            col_beg,
+            col_end,
+            meta,
+            skip,
+            names,
+            references: Vec::new(),
+            min_shift,
+            depth,
+        })
+    }
+
+    pub fn reference(&self, name: &str) -> Option<&ReferenceIndex> {
+        self.names
+            .iter()
+            .position(|x| x == name)
+            .and_then(|n| self.references.get(n))
+    }
+
+    /// Chunks that may hold records overlapping [beg, end), 0-based. Sorted and merged, so each
+    /// record is read at most once.
+    pub fn query(&self, name: &str, beg: usize, end: usize) -> Vec<Chunk> {
+        let reference = match self.reference(name) {
+            Some(x) => x,
+            None => return Vec::new(),
+        };
+
+        let beg = beg as u64;
+        let end = std::cmp::max(end as u64, beg + 1);
+
+        // Nothing before this offset can overlap the region. TBI has it per window, CSI per bin,
+        // where the smallest bin holding beg gives the tightest bound
+        let min_offset = if !reference.linear.is_empty() {
+            let window = (beg >> self.min_shift) as usize;
+            reference
+                .linear
+                .get(window)
+                .or_else(|| reference.linear.last())
+                .copied()
+                .unwrap_or_default()
+        } else {
+            reg2bins(beg, beg + 1, self.min_shift, self.depth)
+                .into_iter()
+                .rev()
+                .find_map(|x| reference.bins.get(&x))
+                .map(|x| x.loffset)
+                .unwrap_or_default()
+        };
+
+        let mut chunks = Vec::new();
+        for bin_number in reg2bins(beg, end, self.min_shift, self.depth) {
+            if let Some(bin) = reference.bins.get(&bin_number) {
+                chunks.extend(bin.chunks.iter().filter(|x| x.end > min_offset).copied());
+            }
+        }
+
+        chunks.sort_by_key(|x| x.start);
+
+        let mut merged: Vec<Chunk> = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            match merged.last_mut() {
+                Some(last) if chunk.start <= last.end => {
+                    last.end = std::cmp::max(last.end, chunk.end);
+                }
+                _ => merged.push(chunk),
+            }
+        }
+
+        merged
+    }
+
+    /// Where the records of a reference start, so the whole reference can be read in one go
+    pub fn reference_start(&self, name: &str) -> Option<VirtualOffset> {
+        let reference = self.reference(name)?;
+        reference.start.or_else(|| {
+            reference
+                .bins
+                .values()
+                .flat_map(|x| x.chunks.iter())
+                .map(|x| x.start)
+                .min()
+        })
+    }
+
+    /// The index only knows about windows with records in them, so this is a rough guess that
+    /// rounds up to the smallest bin size
+    pub fn estimated_length(&self, name: &str) -> usize {
+        let reference = match self.reference(name) {
+            Some(x) => x,
+            None => return 0,
+        };
+
+        if !reference.linear.is_empty() {
+            return reference.linear.len() << self.min_shift;
+        }
+
+        let leaf_level = self.depth;
+        reference
+            .bins
+            .keys()
+            .filter(|&&x| bin_level(x) == Some(leaf_level))
+            .map(|&x| bin_end(x, self.min_shift, self.depth))
+            .max()
+            .unwrap_or_default() as usize
+    }
+}
+
+impl ReferenceIndex {
+    fn add_bin(&mut self, bin_number: u32, mut bin: Bin, chunks: Vec<Chunk>) {
+        if bin_number == PSEUDO_BIN {
+            if chunks.len() == 2 {
+                self.start = Some(chunks[0].start);
+                self.end = Some(chunks[0].end);
+                self.mapped = Some(chunks[1].start.0);
+            }
+            return;
+        }
+
+        bin.chunks = chunks;
+        self.bins.insert(bin_number, bin);
+    }
+}
+
+/// Every bin that can hold a record overlapping [beg, end), 0-based (from the CSI spec)
+pub fn reg2bins(beg: u64, end: u64, min_shift: u32, depth: u32) -> Vec<u32> {
+    let mut bins = Vec::new();
+    let end = end - 1;
+    let mut s = min_shift + depth * 3;
+    let mut t: u64 = 0;
+
+    for l in 0..=depth {
+        let b = t + (beg >> s);
+        let e = t + (end >> s);
+        bins.extend((b..=e).map(|x| x as u32));
+        s -= 3;
+        t += 1 << (l * 3);
+    }
+
+    bins
+}
+
+// Level 0 is the single bin covering everything, depth is the smallest bins
+fn bin_level(bin: u32) -> Option<u32> {
+    let mut first = 0;
+    for level in 0..=10 {
+        let next = first + (1 << (level * 3));
+        if bin < next {
+            return Some(level);
+        }
+        first = next;
+    }
+    None
+}
+
+fn bin_end(bin: u32, min_shift: u32, depth: u32) -> u64 {
+    let level = bin_level(bin).unwrap_or_default();
+    let first = ((1u64 << (level * 3)) - 1) / 7;
+    let size = 1u64 << (min_shift + (depth - level) * 3);
+    (bin as u64 - first + 1) * size
+}
+
+// Little-endian reader over the decompressed index
+struct Bytes<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let x = self.bytes.get(self.position..self.position + n)?;
+        self.position += n;
+        Some(x)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|x| u32::from_le_bytes(x.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        self.take(4)
+            .map(|x| i32::from_le_bytes(x.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|x| u64::from_le_bytes(x.try_into().unwrap()))
+    }
+
+    // Counts are signed in the spec, negative ones mean a broken index
+    fn count(&mut self) -> Option<usize> {
+        usize::try_from(self.i32()?).ok()
+    }
+
+    fn chunks(&mut self) -> Option<Vec<Chunk>> {
+        let n_chunk = self.count()?;
+        // The count is untrusted, a chunk is 16 bytes so there can't be more than are left
+        let remaining = self.bytes.len().saturating_sub(self.position);
+        let mut chunks = Vec::with_capacity(n_chunk.min(remaining / 16));
+        for _ in 0..n_chunk {
+            chunks.push(Chunk {
+                start: VirtualOffset(self.u64()?),
+                end: VirtualOffset(self.u64()?),
+            });
+        }
+        Some(chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks() {
+        let mut bytes = i32::MAX.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&[0; 16]);
+        let mut reader = Bytes {
+            bytes: &bytes,
+            position: 0,
+        };
+        assert!(reader.chunks().is_none());
+
+        let mut bytes = 1i32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&[0; 16]);
+        let mut reader = Bytes {
+            bytes: &bytes,
+            position: 0,
+        };
+        assert_eq!(reader.chunks().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_reg2bins() {
+        // Same as tabix's reg2bins for a region inside the first 16kbp window
+        assert_eq!(
+            reg2bins(0, 100, TBI_MIN_SHIFT, TBI_DEPTH),
+            vec![0, 1, 9, 73, 585, 4681]
+        );
+        assert_eq!(
+            reg2bins(16384, 16385, TBI_MIN_SHIFT, TBI_DEPTH),
+            vec![0, 1, 9, 73, 585, 4682]
+        );
+        assert_eq!(bin_end(4682, TBI_MIN_SHIFT, TBI_DEPTH), 32768);
+        assert_eq!(bin_level(0), Some(0));
+        assert_eq!(bin_level(4681), Some(5));
+    }
+
+    #[test]
+    fn test_read_index() {
+        let tbi = TabixIndex::read("test_data/sorted.gff3.gz.tbi").unwrap();
+        let csi = TabixIndex::read("test_data/sorted.gff3.gz.csi").unwrap();
+
+        for index in [&tbi, &csi] {
+            assert_eq!(index.names, vec!["chr1", "chr2"]);
+            assert_eq!((index.col_seq, index.col_beg, index.col_end), (1, 4, 5));
+            assert_eq!(index.meta, b'#');
+            assert_eq!(index.reference("chr1").unwrap().mapped, Some(5));
+            assert!(index.query("chr3", 0, 100).is_empty());
+        }
+
+        // The gene on chr2 starting at 20000 is past the first window
+        let chunks = tbi.query("chr2", 19999, 21000);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks, csi.query("chr2", 19999, 21000));
+        assert!(chunks[0].start > tbi.reference_start("chr2").unwrap());
+
+        assert_eq!(tbi.estimated_length("chr2"), 32768);
+        assert_eq!(csi.estimated_length("chr2"), 32768);
+    }
+}
@@ -0,0 +1,228 @@
+use super::feature::Feature;
+use crate::structs::Orientation;
+
+// Subfeatures that are pieces of a transcript rather than transcripts of their own
+const PART_TYPES: [&str; 9] = [
+    "exon",
+    "cds",
+    "utr",
+    "five_prime_utr",
+    "three_prime_utr",
+    "start_codon",
+    "stop_codon",
+    "intron",
+    "noncoding_exon",
+];
+
+fn is_part(feature: &Feature) -> bool {
+    PART_TYPES.contains(&feature.feature_type.to_ascii_lowercase().as_str())
+}
+
+fn is_type(feature: &Feature, feature_type: &str) -> bool {
+    feature.feature_type.eq_ignore_ascii_case(feature_type)
+}
+
+/// Genes are replaced by their transcripts (mRNA, ncRNA, ...), everything else is kept whole, so
+/// each of the results can be drawn as one model. Takes the roots from build_hierarchy.
+pub fn transcripts(features: Vec<Feature>) -> Vec<Feature> {
+    let mut found = Vec::new();
+    for feature in features {
+        if feature.children().iter().any(|x| !is_part(x)) {
+            found.extend(
+                feature
+                    .subfeatures
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|x| !is_part(x)),
+            );
+        } else {
+            found.push(feature);
+        }
+    }
+    found.sort_by_key(|x| x.start);
+    found
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockKind {
+    Cds,  // Thick
+    Utr,  // Thin, the non-coding parts of coding transcripts
+    Exon, // Non-coding transcripts, and features without parts
+}
+
+/// Layout of a transcript: blocks where it's transcribed, introns joining them. Coordinates are
+/// 0-based, half-open and sorted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TranscriptModel {
+    pub start: usize,
+    pub end: usize,
+    pub strand: Option<Orientation>,
+    pub blocks: Vec<(usize, usize, BlockKind)>,
+    pub introns: Vec<(usize, usize)>,
+}
+
+fn range(feature: &Feature) -> (usize, usize) {
+    (
+        feature.start.saturating_sub(1),
+        feature.end.max(feature.start),
+    )
+}
+
+// Sorted, with overlapping and touching ranges merged
+fn merge(mut ranges: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+impl TranscriptModel {
+    pub fn new(transcript: &Feature) -> Self {
+        let parts = |feature_type: &str| {
+            merge(
+                transcript
+                    .children()
+                    .iter()
+                    .filter(|x| is_type(x, feature_type))
+                    .map(range)
+                    .collect(),
+            )
+        };
+
+        let exons = parts("exon");
+        let cds = parts("CDS");
+        let utrs = merge(
+            transcript
+                .children()
+                .iter()
+                .filter(|x| x.feature_type.to_ascii_lowercase().ends_with("utr"))
+                .map(range)
+                .collect(),
+        );
+
+        let mut blocks = Vec::new();
+
+        if !exons.is_empty() && !cds.is_empty() {
+            // Exons split into their coding and untranslated parts
+            for (start, end) in exons {
+                let mut position = start;
+                for &(cds_start, cds_end) in cds.iter() {
+                    let (cds_start, cds_end) = (cds_start.max(start), cds_end.min(end));
+                    if cds_start >= cds_end {
+                        continue;
+                    }
+                    if position < cds_start {
+                        blocks.push((position, cds_start, BlockKind::Utr));
+                    }
+                    blocks.push((cds_start, cds_end, BlockKind::Cds));
+                    position = cds_end;
+                }
+                if position < end {
+                    blocks.push((position, end, BlockKind::Utr));
+                }
+            }
+        } else if !exons.is_empty() {
+            blocks.extend(exons.into_iter().map(|(s, e)| (s, e, BlockKind::Exon)));
+        } else if !cds.is_empty() || !utrs.is_empty() {
+            // No exons, as in a lot of prokaryote and some predictor output
+            blocks.extend(cds.into_iter().map(|(s, e)| (s, e, BlockKind::Cds)));
+            blocks.extend(utrs.into_iter().map(|(s, e)| (s, e, BlockKind::Utr)));
+            blocks.sort_unstable_by_key(|x| (x.0, x.1));
+        } else {
+            let (start, end) = range(transcript);
+            blocks.push((start, end, BlockKind::Exon));
+        }
+
+        let introns = blocks
+            .windows(2)
+            .filter(|x| x[0].1 < x[1].0)
+            .map(|x| (x[0].1, x[1].0))
+            .collect();
+
+        let (start, end) = range(transcript);
+        TranscriptModel {
+            start,
+            end,
+            strand: transcript.strand,
+            blocks,
+            introns,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::hierarchy::build_hierarchy;
+
+    fn features(lines: &[&str]) -> Vec<Feature> {
+        build_hierarchy(
+            lines
+                .iter()
+                .map(|x| Feature::from_gff3_line(x).unwrap())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_transcript_model() {
+        let roots = features(&[
+            "chr1\t.\tgene\t100\t900\t.\t-\t.\tID=g1",
+            "chr1\t.\tmRNA\t100\t900\t.\t-\t.\tID=t1;Parent=g1",
+            "chr1\t.\texon\t100\t300\t.\t-\t.\tParent=t1",
+            "chr1\t.\texon\t500\t900\t.\t-\t.\tParent=t1",
+            "chr1\t.\tCDS\t200\t300\t.\t-\t0\tID=c1;Parent=t1",
+            "chr1\t.\tCDS\t500\t700\t.\t-\t2\tID=c1;Parent=t1",
+            "chr1\t.\tncRNA\t100\t400\t.\t-\t.\tID=t2;Parent=g1",
+            "chr1\t.\trepeat_region\t1000\t1100\t.\t.\t.\tID=r1",
+        ]);
+
+        let found = transcripts(roots);
+        assert_eq!(
+            found
+                .iter()
+                .map(|x| x.feature_type.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["mRNA", "ncRNA", "repeat_region"]
+        );
+
+        let model = TranscriptModel::new(&found[0]);
+        assert_eq!((model.start, model.end), (99, 900));
+        assert_eq!(model.strand, Some(Orientation::Negative));
+        assert_eq!(
+            model.blocks,
+            vec![
+                (99, 199, BlockKind::Utr),
+                (199, 300, BlockKind::Cds),
+                (499, 700, BlockKind::Cds),
+                (700, 900, BlockKind::Utr),
+            ]
+        );
+        assert_eq!(model.introns, vec![(300, 499)]);
+
+        // No parts at all
+        let model = TranscriptModel::new(&found[1]);
+        assert_eq!(model.blocks, vec![(99, 400, BlockKind::Exon)]);
+        assert!(model.introns.is_empty());
+
+        // CDS only
+        let roots = features(&[
+            "chr1\t.\tgene\t1\t90\t.\t+\t.\tID=g2",
+            "chr1\t.\tCDS\t1\t30\t.\t+\t0\tParent=g2",
+            "chr1\t.\tCDS\t61\t90\t.\t+\t0\tParent=g2",
+        ]);
+        let found = transcripts(roots);
+        assert_eq!(found.len(), 1);
+        let model = TranscriptModel::new(&found[0]);
+        assert_eq!(
+            model.blocks,
+            vec![(0, 30, BlockKind::Cds), (60, 90, BlockKind::Cds)]
+        );
+        assert_eq!(model.introns, vec![(30, 60)]);
+    }
+}
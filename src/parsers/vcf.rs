@@ -0,0 +1,536 @@
+use bevy::prelude::*;
+
+use std::io::BufRead;
+use std::str::FromStr;
+
+use super::bgzf::*;
+use super::error::*;
+use super::feature::*;
+use super::gff3::*;
+use super::tabix::*;
+
+const VCF_COLUMNS: [&str; 9] = [
+    "CHROM", "POS", "ID", "REF", "ALT", "QUAL", "FILTER", "INFO", "FORMAT",
+];
+
+// ##INFO, ##FORMAT and ##FILTER lines, only what the inspector shows
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeaderField {
+    pub id: String,
+    pub number: Option<String>,
+    pub field_type: Option<String>,
+    pub description: Option<String>,
+}
+
+impl HeaderField {
+    // ##INFO=<ID=DP,Number=1,Type=Integer,Description="Total depth, all samples">
+    fn parse(line: &str) -> Option<Self> {
+        let mut rest = line.split_once("=<")?.1.strip_suffix('>')?;
+        let mut field = HeaderField::default();
+
+        while let Some((key, after)) = rest.split_once('=') {
+            let (value, after) = match after.strip_prefix('"') {
+                Some(x) => {
+                    let (value, after) = x.split_once('"').unwrap_or((x, ""));
+                    (value, after.strip_prefix(',').unwrap_or(after))
+                }
+                None => after.split_once(',').unwrap_or((after, "")),
+            };
+
+            match key {
+                "ID" => field.id = value.to_string(),
+                "Number" => field.number = Some(value.to_string()),
+                "Type" => field.field_type = Some(value.to_string()),
+                "Description" => field.description = Some(value.to_string()),
+                _ => (),
+            }
+
+            rest = after;
+        }
+
+        if field.id.is_empty() {
+            None
+        } else {
+            Some(field)
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VcfHeader {
+    pub file_format: Option<String>, // VCFv4.2 and so on
+    pub info: Vec<HeaderField>,
+    pub format: Vec<HeaderField>,
+    pub filters: Vec<HeaderField>,
+    pub samples: Vec<String>, // In the order of the sample columns
+}
+
+impl VcfHeader {
+    /// Reads the ## lines and the #CHROM line, the file has to have the latter
+    pub fn read(filename: &str) -> Result<VcfHeader, ParseError> {
+        let mut file =
+            InputFile::open(filename).map_err(|x| ParseError::from(x).with_file(filename))?;
+
+        let mut header = VcfHeader::default();
+        let mut line = String::new();
+        let mut line_number = 0;
+
+        loop {
+            line.clear();
+            line_number += 1;
+
+            let bytes_read = file
+                .read_line(&mut line)
+                .map_err(|x| ParseError::from(x).at_line(line_number).with_file(filename))?;
+
+            let x = line.trim_end();
+            if bytes_read == 0 || !x.starts_with('#') {
+                return Err(ParseError::new(ParseErrorKind::MissingField)
+                    .with_field(1, "#CHROM header")
+                    .at_line(line_number)
+                    .with_file(filename));
+            }
+
+            if let Some(x) = x.strip_prefix("##fileformat=") {
+                header.file_format = Some(x.to_string());
+            } else if x.starts_with("##INFO=") {
+                header.info.extend(HeaderField::parse(x));
+            } else if x.starts_with("##FORMAT=") {
+                header.format.extend(HeaderField::parse(x));
+            } else if x.starts_with("##FILTER=") {
+                header.filters.extend(HeaderField::parse(x));
+            } else if x.starts_with("#CHROM") {
+                header.samples = x
+                    .split('\t')
+                    .skip(VCF_COLUMNS.len())
+                    .map(|x| x.to_string())
+                    .collect();
+                return Ok(header);
+            }
+        }
+    }
+
+    pub fn info_field(&self, id: &str) -> Option<&HeaderField> {
+        self.info.iter().find(|x| x.id == id)
+    }
+
+    pub fn format_field(&self, id: &str) -> Option<&HeaderField> {
+        self.format.iter().find(|x| x.id == id)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VariantKind {
+    Snv,
+    Mnv,
+    Insertion,
+    Deletion,
+    Complex,    // Alleles of different kinds, or neither longer nor the same length
+    Structural, // Symbolic (<DEL>) and breakend alleles
+    Reference,  // No alternate allele, as in gVCF reference blocks
+}
+
+impl VariantKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VariantKind::Snv => "SNV",
+            VariantKind::Mnv => "MNV",
+            VariantKind::Insertion => "insertion",
+            VariantKind::Deletion => "deletion",
+            VariantKind::Complex => "complex",
+            VariantKind::Structural => "structural variant",
+            VariantKind::Reference => "reference",
+        }
+    }
+
+    fn of_allele(reference: &str, alternate: &str) -> Self {
+        if alternate.starts_with('<') || alternate.contains('[') || alternate.contains(']') {
+            return VariantKind::Structural;
+        }
+
+        match (reference.len(), alternate.len()) {
+            (1, 1) => VariantKind::Snv,
+            (r, a) if r == a => VariantKind::Mnv,
+            // Indels share their first base with the reference
+            (r, a) if r < a && alternate.starts_with(reference) => VariantKind::Insertion,
+            (r, a) if r > a && reference.starts_with(alternate) => VariantKind::Deletion,
+            _ => VariantKind::Complex,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GenotypeCall {
+    HomRef,
+    Het,
+    HomAlt,
+    NoCall,
+}
+
+/// GT, allele indices with None for missing (.), phased when separated by |
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Genotype {
+    pub alleles: Vec<Option<usize>>,
+    pub phased: bool,
+}
+
+impl FromStr for Genotype {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let alleles = s
+            .split(|x| x == '/' || x == '|')
+            .map(|x| match x {
+                "." => Ok(None),
+                x => x.parse::<usize>().map(Some).map_err(|_| ()),
+            })
+            .collect::<Result<Vec<Option<usize>>, ()>>()?;
+
+        Ok(Genotype {
+            alleles,
+            phased: s.contains('|'),
+        })
+    }
+}
+
+impl Genotype {
+    pub fn call(&self) -> GenotypeCall {
+        let called = self.alleles.iter().flatten().collect::<Vec<&usize>>();
+        match called.first() {
+            None => GenotypeCall::NoCall,
+            Some(0) if called.iter().all(|x| **x == 0) => GenotypeCall::HomRef,
+            Some(first) if called.iter().all(|x| x == first) => GenotypeCall::HomAlt,
+            _ => GenotypeCall::Het,
+        }
+    }
+}
+
+/// A VCF record. Sample columns are kept as written and split when asked for, most are never
+/// looked at.
+#[derive(Clone, Debug, Default, PartialEq, Component)]
+pub struct Variant {
+    pub landmark: String,
+    pub position: usize, // 1-based, of the first reference base
+    pub id: Option<String>,
+    pub reference: String,
+    pub alternates: Vec<String>,
+    pub quality: Option<f64>,
+    pub filter: Option<String>,
+    pub info: Vec<(String, Option<String>)>, // Flags have no value
+    pub format: Vec<String>,
+    pub samples: Vec<String>,
+}
+
+impl Variant {
+    pub fn from_vcf_line(line: &str) -> Result<Variant, ParseError> {
+        let split = line.split('\t').collect::<Vec<&str>>();
+        if split.len() < 8 {
+            return Err(ParseError::new(ParseErrorKind::MissingField)
+                .with_field(split.len() + 1, VCF_COLUMNS[split.len()]));
+        }
+
+        let invalid = |kind: ParseErrorKind, n: usize| {
+            ParseError::new(kind).with_field(n + 1, VCF_COLUMNS[n])
+        };
+        let missing = |x: &str| if x == "." { None } else { Some(x.to_string()) };
+
+        let position =
+            usize::from_str(split[1]).map_err(|_| invalid(ParseErrorKind::InvalidInteger, 1))?;
+
+        if split[3].is_empty() || split[3] == "." {
+            return Err(invalid(ParseErrorKind::InvalidValue, 3));
+        }
+
+        let alternates = match split[4] {
+            "." => Vec::new(),
+            x => x.split(',').map(|x| x.to_string()).collect(),
+        };
+
+        let quality = match split[5] {
+            "." => None,
+            x => Some(f64::from_str(x).map_err(|_| invalid(ParseErrorKind::InvalidFloat, 5))?),
+        };
+
+        let info = match split[7] {
+            "." | "" => Vec::new(),
+            x => x
+                .split(';')
+                .filter(|x| !x.is_empty())
+                .map(|x| match x.split_once('=') {
+                    Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                    None => (x.to_string(), None),
+                })
+                .collect(),
+        };
+
+        let format = split
+            .get(8)
+            .map(|x| x.split(':').map(|x| x.to_string()).collect())
+            .unwrap_or_default();
+
+        Ok(Variant {
+            landmark: split[0].to_string(),
+            position,
+            id: missing(split[2]),
+            reference: split[3].to_string(),
+            alternates,
+            quality,
+            filter: missing(split[6]),
+            info,
+            format,
+            samples: split.iter().skip(9).map(|x| x.to_string()).collect(),
+        })
+    }
+
+    pub fn info(&self, key: &str) -> Option<&str> {
+        self.info
+            .iter()
+            .find(|x| x.0 == key)
+            .and_then(|x| x.1.as_deref())
+    }
+
+    /// 1-based and inclusive, END when given (structural variants, reference blocks)
+    pub fn end(&self) -> usize {
+        self.info("END")
+            .and_then(|x| x.parse::<usize>().ok())
+            .unwrap_or(self.position + self.reference.len() - 1)
+            .max(self.position)
+    }
+
+    pub fn kind(&self) -> VariantKind {
+        if self.info("SVTYPE").is_some() {
+            return VariantKind::Structural;
+        }
+
+        // Spanning deletions (*) are another record's
+        let mut kinds = self
+            .alternates
+            .iter()
+            .filter(|x| *x != "*")
+            .map(|x| VariantKind::of_allele(&self.reference, x));
+
+        match kinds.next() {
+            None => VariantKind::Reference,
+            Some(first) if kinds.all(|x| x == first) => first,
+            Some(_) => VariantKind::Complex,
+        }
+    }
+
+    /// A sample's value for a FORMAT key, None when missing
+    pub fn sample_field(&self, sample: usize, key: &str) -> Option<&str> {
+        let n = self.format.iter().position(|x| x == key)?;
+        self.samples
+            .get(sample)?
+            .split(':')
+            .nth(n)
+            .filter(|x| *x != ".")
+    }
+
+    pub fn genotype(&self, sample: usize) -> Option<Genotype> {
+        self.sample_field(sample, "GT")?.parse().ok()
+    }
+
+    /// For density, counting and the search index (by ID)
+    pub fn to_feature(&self) -> Feature {
+        let mut feature = Feature {
+            landmark: self.landmark.clone(),
+            source: "VCF".to_string(),
+            feature_type: self.kind().name().to_string(),
+            start: self.position,
+            end: self.end(),
+            score: self.quality,
+            ..Default::default()
+        };
+
+        if let Some(id) = &self.id {
+            feature.name = id.clone();
+            feature.attributes.id = Some(id.clone());
+        }
+
+        feature
+    }
+}
+
+impl Record for Variant {
+    fn landmark(&self) -> &str {
+        &self.landmark
+    }
+
+    fn range(&self) -> (usize, usize) {
+        (self.position, self.end())
+    }
+}
+
+// CHROM, POS and the length of REF, enough to index the file
+fn parse_vcf_coordinates(line: &str) -> Result<(&str, usize, usize), ParseError> {
+    let split = line.splitn(5, '\t').collect::<Vec<&str>>();
+    if split.len() < 4 {
+        return Err(ParseError::new(ParseErrorKind::MissingField)
+            .with_field(split.len() + 1, VCF_COLUMNS[split.len()]));
+    }
+
+    let position = usize::from_str(split[1])
+        .map_err(|_| ParseError::new(ParseErrorKind::InvalidInteger).with_field(2, "POS"))?;
+
+    Ok((split[0], position, position + split[3].len().max(1) - 1))
+}
+
+/// Multi-sample VCF, plain, gzipped or BGZF (with or without a tabix index). Records are read a
+/// landmark at a time, as with GFF3.
+#[derive(Clone, Debug)]
+pub struct Vcf {
+    pub filename: String,
+    pub header: VcfHeader,
+    pub landmarks: Vec<Landmark>,
+    pub index: Option<TabixIndex>,
+    pub mode: ParseMode,
+    pub warnings: Warnings,
+}
+
+impl Vcf {
+    pub fn parse<T>(filename: T) -> Result<Vcf, ParseError>
+    where
+        T: ToString,
+    {
+        Vcf::parse_with(filename, &ParseOptions::default())
+    }
+
+    pub fn parse_with<T>(filename: T, options: &ParseOptions) -> Result<Vcf, ParseError>
+    where
+        T: ToString,
+    {
+        let filename = filename.to_string();
+        let header = VcfHeader::read(&filename)?;
+        let (landmarks, index, warnings) =
+            open_landmarks(&filename, options, parse_vcf_coordinates)?;
+
+        Ok(Vcf {
+            filename,
+            header,
+            landmarks,
+            index,
+            mode: options.mode,
+            warnings,
+        })
+    }
+
+    /// Skips what the parse mode recovers from, parse_region_with_warnings says what that was
+    pub fn parse_region(&self, landmark: &str) -> Result<Vec<Variant>, ParseError> {
+        let mut warnings = Warnings::default();
+        self.parse_region_with_warnings(landmark, &mut warnings)
+    }
+
+    pub fn parse_region_with_warnings(
+        &self,
+        landmark: &str,
+        warnings: &mut Warnings,
+    ) -> Result<Vec<Variant>, ParseError> {
+        let region = match self.landmarks.iter().find(|x| x.id == landmark) {
+            Some(x) => x,
+            None => return Ok(Vec::new()),
+        };
+
+        read_region(
+            &self.filename,
+            region,
+            self.mode,
+            warnings,
+            Variant::from_vcf_line,
+        )
+    }
+
+    /// Variants overlapping start..=end (1-based), using the tabix index when there is one
+    pub fn parse_range(
+        &self,
+        landmark: &str,
+        start: usize,
+        end: usize,
+        warnings: &mut Warnings,
+    ) -> Result<Vec<Variant>, ParseError> {
+        let region = match self.landmarks.iter().find(|x| x.id == landmark) {
+            Some(x) => x,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut variants = read_range(
+            &self.filename,
+            region,
+            self.index.as_ref(),
+            start,
+            end,
+            self.mode,
+            warnings,
+            Variant::from_vcf_line,
+        )?;
+
+        variants.retain(|x| x.range().0 <= end && start <= x.range().1);
+        Ok(variants)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vcf_line() {
+        let variant = Variant::from_vcf_line(
+            "chr1\t100\trs1\tA\tG,T\t50.5\tPASS\tDP=30;DB\tGT:DP\t0/1:10\t1|1:12\t./.:.",
+        )
+        .unwrap();
+        assert_eq!(variant.id.as_deref(), Some("rs1"));
+        assert_eq!(variant.alternates, vec!["G", "T"]);
+        assert_eq!(variant.quality, Some(50.5));
+        assert_eq!(variant.info("DP"), Some("30"));
+        assert!(variant.info.contains(&("DB".to_string(), None)));
+        assert_eq!(variant.kind(), VariantKind::Snv);
+        assert_eq!(variant.end(), 100);
+
+        assert_eq!(variant.genotype(0).unwrap().call(), GenotypeCall::Het);
+        assert!(variant.genotype(1).unwrap().phased);
+        assert_eq!(variant.genotype(1).unwrap().call(), GenotypeCall::HomAlt);
+        assert_eq!(variant.genotype(2).unwrap().call(), GenotypeCall::NoCall);
+        assert_eq!(variant.sample_field(1, "DP"), Some("12"));
+        assert_eq!(variant.sample_field(2, "DP"), None);
+        assert_eq!(variant.genotype(3), None);
+
+        let kind = |line: &str| Variant::from_vcf_line(line).unwrap().kind();
+        assert_eq!(kind("chr1\t1\t.\tAT\tA\t.\t.\t."), VariantKind::Deletion);
+        assert_eq!(kind("chr1\t1\t.\tA\tATT\t.\t.\t."), VariantKind::Insertion);
+        assert_eq!(kind("chr1\t1\t.\tAT\tGC\t.\t.\t."), VariantKind::Mnv);
+        assert_eq!(kind("chr1\t1\t.\tA\tG,AT\t.\t.\t."), VariantKind::Complex);
+        assert_eq!(
+            kind("chr1\t1\t.\tA\t<DEL>\t.\t.\tEND=500"),
+            VariantKind::Structural
+        );
+        assert_eq!(
+            kind("chr1\t1\t.\tA\t.\t.\t.\tEND=90"),
+            VariantKind::Reference
+        );
+
+        assert!(Variant::from_vcf_line("chr1\t100\trs1\tA\tG").is_err());
+        assert!(Variant::from_vcf_line("chr1\tx\trs1\tA\tG\t.\t.\t.").is_err());
+    }
+
+    #[test]
+    fn test_parse_vcf() {
+        let vcf = Vcf::parse("test_data/test.vcf").unwrap();
+        assert_eq!(vcf.header.file_format.as_deref(), Some("VCFv4.2"));
+        assert_eq!(vcf.header.samples, vec!["NA00001", "NA00002", "NA00003"]);
+        assert_eq!(
+            vcf.header.info_field("DP").unwrap().description.as_deref(),
+            Some("Total Depth, all samples")
+        );
+        assert_eq!(vcf.landmarks.len(), 2);
+
+        let variants = vcf.parse_region("20").unwrap();
+        assert_eq!(variants.len(), 5);
+        assert_eq!(variants[4].kind(), VariantKind::Complex);
+        assert_eq!(vcf.parse_region("X").unwrap().len(), 1);
+
+        // Same records through BGZF
+        let compressed = Vcf::parse("test_data/test.vcf.gz").unwrap();
+        assert_eq!(compressed.header.samples, vcf.header.samples);
+        assert_eq!(compressed.parse_region("20").unwrap(), variants);
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::loading::FileKind;
+
+/// What to open and where to start. Session files have one entry per line, a kind and a value:
+///
+/// ```text
+/// # Comments and blank lines are skipped
+/// gfa graph.gfa
+/// gff3 annotation.gff3
+/// locus chr1:10,000-20,000
+/// ```
+///
+/// Relative paths are from the directory the session file is in.
+#[derive(Clone, Debug, Default)]
+pub struct Session {
+    pub inputs: Vec<(FileKind, String)>,
+    pub locus: Option<String>,
+}
+
+impl Session {
+    pub fn load(filename: &str) -> Result<Self, String> {
+        let file = File::open(filename).map_err(|x| format!("{}: {}", filename, x))?;
+        let dir = Path::new(filename)
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+        let mut session = Session::default();
+
+        for (n, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|x| format!("{}: {}", filename, x))?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (kind, value) = match line.split_once(char::is_whitespace) {
+                Some((kind, value)) if !value.trim().is_empty() => (kind, value.trim()),
+                _ => {
+                    return Err(format!(
+                        "{}:{}: expected a kind and a value",
+                        filename,
+                        n + 1
+                    ))
+                }
+            };
+
+            if kind == "locus" {
+                session.locus = Some(value.to_string());
+            } else {
+                session
+                    .add(kind, &dir.join(value).to_string_lossy())
+                    .map_err(|x| format!("{}:{}: {}", filename, n + 1, x))?;
+            }
+        }
+
+        Ok(session)
+    }
+
+    pub fn add(&mut self, kind: &str, filename: &str) -> Result<(), String> {
+        let kind = FileKind::from_name(kind).ok_or_else(|| format!("unknown kind {}", kind))?;
+        self.inputs.push((kind, filename.to_string()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let session = Session::load("test_data/test.session").unwrap();
+        assert_eq!(
+            session.inputs,
+            vec![
+                (FileKind::Gfa, "test_data/test.gfa".to_string()),
+                (FileKind::Gff3, "test_data/sorted.gff3".to_string()),
+                (FileKind::Vcf, "/data/calls.vcf".to_string()),
+            ]
+        );
+        assert_eq!(session.locus.as_deref(), Some("chr1:10,000-20,000"));
+
+        let error = Session::load("test_data/malformed.session").unwrap_err();
+        assert!(error.starts_with("test_data/malformed.session:4:"));
+        assert_eq!(
+            Session::load("test_data/unknown.session").unwrap_err(),
+            "test_data/unknown.session:2: unknown kind bam"
+        );
+        assert!(Session::load("test_data/missing.session").is_err());
+    }
+}
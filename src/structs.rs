@@ -3,6 +3,7 @@ use crate::parsers::*;
 use std::collections::HashMap;
 use std::num::NonZeroUsize;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use bevy::prelude::*;
 use twox_hash::RandomXxHashBuilder64;
@@ -10,10 +11,19 @@ use twox_hash::RandomXxh3HashBuilder64;
 
 pub struct CameraMoved;
 
+// A track was reordered, hidden or otherwise changed, its features are laid out again
+pub struct TracksChanged;
+
 pub struct LoadLandmark {
     pub id: String,
 }
 
+// Moves the sequence view camera to show exactly [start, end) of the landmark, 0-based
+pub struct FrameRange {
+    pub start: usize,
+    pub end: usize,
+}
+
 #[derive(Component)]
 pub struct Collider {
     pub size: Vec2,
@@ -48,12 +58,21 @@ impl ID {
     }
 }
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum Orientation {
     Positive,
     Negative,
 }
 
+impl Orientation {
+    pub fn flip(self) -> Self {
+        match self {
+            Orientation::Positive => Orientation::Negative,
+            Orientation::Negative => Orientation::Positive,
+        }
+    }
+}
+
 impl Default for Orientation {
     fn default() -> Self {
         Orientation::Positive
@@ -83,7 +102,23 @@ pub struct Link {
     pub from_orient: Orientation,
     pub to: String,
     pub to_orient: Orientation,
-    pub overlap: Option<String>,
+    pub overlap: Option<Cigar>,
+    pub mapping_quality: Option<u8>,          // MQ:i
+    pub mismatches: Option<usize>,            // NM:i
+    pub read_count: Option<NonZeroUsize>,     // RC:i
+    pub fragment_count: Option<NonZeroUsize>, // FC:i
+    pub kmer_count: Option<NonZeroUsize>,     // KC:i
+    pub id: Option<String>,                   // ID:Z
+}
+
+impl Link {
+    /// Whichever count the assembler gave, reads first
+    pub fn coverage(&self) -> Option<usize> {
+        self.read_count
+            .or(self.kmer_count)
+            .or(self.fragment_count)
+            .map(|x| x.get())
+    }
 }
 
 pub struct LinkEntities {
@@ -106,22 +141,345 @@ pub struct Segment {
     pub orientation: Option<Orientation>, // For genes, CDS, etc... None when not applicable...
 }
 
+// H line
+#[derive(Clone, Debug, Default)]
+pub struct Header {
+    pub version: Option<String>, // VN:Z: field
+    pub tags: Vec<String>,       // Any other tags, kept as-is
+}
+
+// P line
+#[derive(Clone, Debug, Default)]
+pub struct Path {
+    pub name: String,
+    pub segments: Vec<(String, Orientation)>,
+    pub overlaps: Option<Vec<String>>, // None when the overlaps field is "*"
+}
+
+// W line (GFA 1.1)
+#[derive(Clone, Debug, Default)]
+pub struct Walk {
+    pub sample: String,
+    pub haplotype: usize,
+    pub sequence: String,
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+    pub segments: Vec<(String, Orientation)>,
+}
+
+impl Walk {
+    // PanSN-style name, so walks can be looked up just like paths
+    pub fn name(&self) -> String {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => format!(
+                "{}#{}#{}:{}-{}",
+                self.sample, self.haplotype, self.sequence, start, end
+            ),
+            _ => format!("{}#{}#{}", self.sample, self.haplotype, self.sequence),
+        }
+    }
+}
+
+// C line
+#[derive(Clone, Debug, Default)]
+pub struct Containment {
+    pub container: String,
+    pub container_orient: Orientation,
+    pub contained: String,
+    pub contained_orient: Orientation,
+    pub pos: usize,
+    pub overlap: Option<Cigar>,
+}
+
+// From: https://github.com/GFA-spec/GFA-spec/blob/master/GFA2.md
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GfaVersion {
+    V1, // 1.0, 1.1 and 1.2
+    V2,
+}
+
+impl Default for GfaVersion {
+    fn default() -> Self {
+        GfaVersion::V1
+    }
+}
+
+// GFA2 positions may carry a trailing $ marking the end of the sequence
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    pub pos: usize,
+    pub is_end: bool,
+}
+
+impl FromStr for Position {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pos, is_end) = match s.strip_suffix('$') {
+            Some(x) => (x, true),
+            None => (s, false),
+        };
+
+        Ok(Position {
+            pos: pos.parse::<usize>().map_err(|_| ())?,
+            is_end,
+        })
+    }
+}
+
+// F line (GFA2)
+#[derive(Clone, Debug, Default)]
+pub struct Fragment {
+    pub segment: String,
+    pub external: String,
+    pub external_orient: Orientation,
+    pub segment_range: (Position, Position),
+    pub fragment_range: (Position, Position),
+    pub alignment: Option<Cigar>,
+}
+
+// G line (GFA2)
+#[derive(Clone, Debug, Default)]
+pub struct Gap {
+    pub id: Option<String>,
+    pub from: String,
+    pub from_orient: Orientation,
+    pub to: String,
+    pub to_orient: Orientation,
+    pub distance: i64,
+    pub variance: Option<usize>,
+}
+
+// O and U lines (GFA2)
+#[derive(Clone, Debug, Default)]
+pub struct Group {
+    pub id: String,
+    pub ordered: bool,
+    pub members: Vec<(String, Option<Orientation>)>, // Orientation is only given in ordered groups
+}
+
+// Annotation files a track can be drawn from
+#[derive(Clone, Debug)]
+pub enum TrackSource {
+    Gff3(Gff3),
+    Gtf(Gtf),
+    Bed(Bed),
+    Vcf(Vcf),
+}
+
+impl TrackSource {
+    pub fn filename(&self) -> &str {
+        match self {
+            TrackSource::Gff3(x) => &x.filename,
+            TrackSource::Gtf(x) => &x.filename,
+            TrackSource::Bed(x) => &x.filename,
+            TrackSource::Vcf(x) => &x.filename,
+        }
+    }
+
+    pub fn landmarks(&self) -> &[Landmark] {
+        match self {
+            TrackSource::Gff3(x) => &x.landmarks,
+            TrackSource::Gtf(x) => &x.landmarks,
+            TrackSource::Bed(x) => &x.landmarks,
+            TrackSource::Vcf(x) => &x.landmarks,
+        }
+    }
+
+    pub fn parse_region(&self, landmark: &str) -> Result<Vec<Feature>, ParseError> {
+        match self {
+            TrackSource::Gff3(x) => x.parse_region(landmark),
+            TrackSource::Gtf(x) => x.parse_region(landmark),
+            TrackSource::Bed(x) => x.parse_region(landmark),
+            // As features for searching by ID, the sequence view reads the variants themselves
+            TrackSource::Vcf(x) => x
+                .parse_region(landmark)
+                .map(|x| x.iter().map(Variant::to_feature).collect()),
+        }
+    }
+}
+
+// Like IGV's, all but Density become a histogram when zoomed out far enough
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisplayMode {
+    Collapsed, // Features on one row, overlapping
+    Squished,  // A thin row each, packed
+    Expanded,  // A full row each, packed
+    Density,   // Always the histogram
+}
+
+impl DisplayMode {
+    pub const ALL: [DisplayMode; 4] = [
+        DisplayMode::Collapsed,
+        DisplayMode::Squished,
+        DisplayMode::Expanded,
+        DisplayMode::Density,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DisplayMode::Collapsed => "Collapsed",
+            DisplayMode::Squished => "Squished",
+            DisplayMode::Expanded => "Expanded",
+            DisplayMode::Density => "Density",
+        }
+    }
+
+    /// Height of a row of packed features, glyphs take half of it
+    pub fn row_height(&self) -> Option<f32> {
+        match self {
+            DisplayMode::Squished => Some(0.1),
+            DisplayMode::Expanded => Some(0.4),
+            _ => None,
+        }
+    }
+}
+
+// Top of the first lane, the reference bar is above it
+pub const TRACKS_TOP: f32 = -1.0;
+
+#[derive(Clone, Debug)]
+pub struct Track {
+    pub id: usize, // Stays the same through renames and reordering
+    pub name: String,
+    pub source: Arc<TrackSource>, // Shared with the threads indexing it
+    pub mode: DisplayMode,
+    pub height: f32, // Of its lane, in world units. Packed rows can make it taller.
+    pub visible: bool,
+    pub rows: usize, // Packed on the landmark open in the sequence view
+}
+
+impl Track {
+    pub fn lane_height(&self) -> f32 {
+        match self.mode.row_height() {
+            Some(x) => self.height.max(self.rows as f32 * x),
+            None => self.height,
+        }
+    }
+}
+
 pub struct BrowserState {
     pub landmark: Option<(String, usize)>, // ID, length
-    pub gff3: Option<Gff3>,
+    pub tracks: Vec<Track>,                // Top to bottom
     pub gfa: Option<Gfa>,
+    pub fasta: Option<Fasta>,
+    next_track: usize,
+}
+
+impl BrowserState {
+    /// Length of a landmark, or GFA segment, from whichever file knows it best
+    pub fn landmark_length(&self, id: &str) -> Option<usize> {
+        if let Some(x) = self.fasta.as_ref().and_then(|x| x.length(id)) {
+            return Some(x);
+        }
+
+        // Annotations only know the largest coordinate in them, take the largest of those
+        let annotations = self
+            .tracks
+            .iter()
+            .filter_map(|x| x.source.landmarks().iter().find(|x| x.id == id))
+            .map(|x| x.length)
+            .max();
+        if annotations.is_some() {
+            return annotations;
+        }
+
+        self.gfa.as_ref().and_then(|x| x.lengths.get(id).copied())
+    }
+
+    /// Named after the file, or its BED track line, returns the track's ID
+    pub fn add_track(&mut self, source: TrackSource) -> usize {
+        let id = self.next_track;
+        self.next_track += 1;
+
+        let track_line = match &source {
+            TrackSource::Bed(x) => x.track.name.clone(),
+            _ => None,
+        };
+        let name = track_line.unwrap_or_else(|| {
+            std::path::Path::new(source.filename())
+                .file_name()
+                .map(|x| x.to_string_lossy().to_string())
+                .unwrap_or_else(|| source.filename().to_string())
+        });
+
+        self.tracks.push(Track {
+            id,
+            name,
+            source: Arc::new(source),
+            mode: DisplayMode::Collapsed,
+            height: 2.0,
+            visible: true,
+            rows: 1,
+        });
+        id
+    }
+
+    pub fn track(&self, id: usize) -> Option<&Track> {
+        self.tracks.iter().find(|x| x.id == id)
+    }
+
+    /// Top and bottom of the track's lane, hidden tracks don't have one
+    pub fn lane(&self, id: usize) -> Option<(f32, f32)> {
+        let mut top = TRACKS_TOP;
+        for track in self.tracks.iter().filter(|x| x.visible) {
+            if track.id == id {
+                return Some((top, top - track.lane_height()));
+            }
+            top -= track.lane_height();
+        }
+        None
+    }
 }
 
 impl Default for BrowserState {
     fn default() -> BrowserState {
         BrowserState {
             landmark: None,
-            gff3: None,
+            tracks: Vec::new(),
             gfa: None,
+            fasta: None,
+            next_track: 0,
         }
     }
 }
 
+// Features of one track on the landmark open in the sequence view, only the visible ones are
+// spawned
+#[derive(Default)]
+pub struct FeatureIndex {
+    pub landmark: String,
+    pub track: usize,
+    pub features: IntervalIndex<Feature>, // Packed, see IntervalIndex::pack_features
+    pub variants: IntervalIndex<Variant>, // VCF tracks have these instead of features
+    pub rows: usize,
+    pub spawned: HashMap<usize, Entity, RandomXxh3HashBuilder64>, // Position in either
+}
+
+impl FeatureIndex {
+    pub fn count_overlapping(&self, start: usize, end: usize) -> usize {
+        self.features.count_overlapping(start, end) + self.variants.count_overlapping(start, end)
+    }
+
+    /// Positions in whichever of the two the track has, they never both do
+    pub fn overlapping_positions(&self, start: usize, end: usize) -> Vec<usize> {
+        let mut found = self.features.overlapping_positions(start, end);
+        found.extend(self.variants.overlapping_positions(start, end));
+        found
+    }
+}
+
+// Which track a spawned feature belongs to
+#[derive(Component)]
+pub struct InTrack(pub usize);
+
+// The quad drawn between two segments
+#[derive(Component)]
+pub struct LinkLine {
+    pub from: Entity,
+    pub to: Entity,
+}
+
 pub enum View {
     SequenceOverview,
     Chromosome,
@@ -129,10 +487,33 @@ pub enum View {
     Protein,
 }
 
+// How tracks are drawn in the sequence view, from zoomed out to zoomed in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DetailLevel {
+    Density, // Binned feature counts
+    Glyphs,  // One entity per feature
+    Bases,   // Glyphs, plus the sequence when a FASTA is loaded
+}
+
 pub struct UISetting {
-    pub zoom_factor: f32,
+    pub zoom_factor: f32, // 1 + the camera's x scale, speeds up panning when zoomed out
     pub view: View,
     pub dragging: Option<i32>,
+    pub density_span: f32, // Visible bp past which tracks become histograms
+    pub bases_span: f32,   // Visible bp under which bases are drawn
+}
+
+impl UISetting {
+    /// From the width of the visible range in bp, see visible_range
+    pub fn detail_level(&self, span: f32) -> DetailLevel {
+        if span > self.density_span {
+            DetailLevel::Density
+        } else if span < self.bases_span {
+            DetailLevel::Bases
+        } else {
+            DetailLevel::Glyphs
+        }
+    }
 }
 
 impl Default for UISetting {
@@ -141,6 +522,8 @@ impl Default for UISetting {
             zoom_factor: 1.0,
             view: View::SequenceOverview,
             dragging: None,
+            density_span: 1_000_000.0,
+            bases_span: 150.0,
         }
     }
 }
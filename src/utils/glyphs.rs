@@ -0,0 +1,133 @@
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use twox_hash::RandomXxh3HashBuilder64;
+
+use std::collections::HashMap;
+
+/// Mesh and material handles shared by every glyph. Rectangles are the unit quad scaled by their
+/// Transform, so there is one mesh for all of them and one material per color.
+pub struct GlyphAssets {
+    pub quad: Handle<Mesh>,
+    pub chevron: Handle<Mesh>, // Points to +x, rotate it half a turn about z for -x
+    materials: HashMap<(u32, bool), Handle<StandardMaterial>, RandomXxh3HashBuilder64>,
+}
+
+impl FromWorld for GlyphAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        GlyphAssets {
+            quad: meshes.add(Mesh::from(shape::Quad {
+                size: Vec2::ONE,
+                flip: false,
+            })),
+            chevron: meshes.add(chevron()),
+            materials: HashMap::default(),
+        }
+    }
+}
+
+impl GlyphAssets {
+    pub fn material(
+        &mut self,
+        materials: &mut Assets<StandardMaterial>,
+        color: Color,
+    ) -> Handle<StandardMaterial> {
+        self.get_or_add(materials, color, false)
+    }
+
+    /// Glows, for links
+    pub fn emissive_material(
+        &mut self,
+        materials: &mut Assets<StandardMaterial>,
+        color: Color,
+    ) -> Handle<StandardMaterial> {
+        self.get_or_add(materials, color, true)
+    }
+
+    fn get_or_add(
+        &mut self,
+        materials: &mut Assets<StandardMaterial>,
+        color: Color,
+        emissive: bool,
+    ) -> Handle<StandardMaterial> {
+        self.materials
+            .entry((color.as_rgba_u32(), emissive))
+            .or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color: color,
+                    emissive: if emissive {
+                        Color::WHITE * 10.0f32
+                    } else {
+                        Color::BLACK
+                    },
+                    ..Default::default()
+                })
+            })
+            .clone()
+    }
+}
+
+/// Transform placing the unit quad as a size.x by size.y rectangle centered on center
+pub fn rectangle(center: Vec3, size: Vec2) -> Transform {
+    Transform::from_translation(center).with_scale(size.extend(1.0))
+}
+
+/// A > in the unit square, for strand on introns
+pub fn chevron() -> Mesh {
+    // Two arms meeting at (0.5, 0), a quarter of the width thick
+    let positions = vec![
+        [-0.5, 0.5, 0.0],
+        [-0.25, 0.5, 0.0],
+        [0.5, 0.0, 0.0],
+        [0.25, 0.0, 0.0],
+        [-0.5, -0.5, 0.0],
+        [-0.25, -0.5, 0.0],
+    ];
+    let uvs = positions
+        .iter()
+        .map(|x| [x[0] + 0.5, 0.5 - x[1]])
+        .collect::<Vec<[f32; 2]>>();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        vec![[0.0, 0.0, 1.0]; positions.len()],
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(vec![0, 3, 2, 0, 2, 1, 4, 5, 2, 4, 2, 3])));
+    mesh
+}
+
+/// Any number of rectangles (center, size) on the z = 0 plane as a single mesh, one draw call
+/// for things that don't have to be picked one by one
+pub fn batched_rectangles(rectangles: &[(Vec2, Vec2)]) -> Mesh {
+    let mut positions = Vec::with_capacity(rectangles.len() * 4);
+    let mut normals = Vec::with_capacity(rectangles.len() * 4);
+    let mut uvs = Vec::with_capacity(rectangles.len() * 4);
+    let mut indices = Vec::with_capacity(rectangles.len() * 6);
+
+    for (center, size) in rectangles {
+        let min = *center - *size / 2.0;
+        let max = *center + *size / 2.0;
+        let first = positions.len() as u32;
+
+        // Same winding as shape::Quad
+        positions.extend([
+            [min.x, min.y, 0.0],
+            [min.x, max.y, 0.0],
+            [max.x, max.y, 0.0],
+            [max.x, min.y, 0.0],
+        ]);
+        normals.extend([[0.0, 0.0, 1.0]; 4]);
+        uvs.extend([[0.0, 1.0], [0.0, 0.0], [1.0, 0.0], [1.0, 1.0]]);
+        indices.extend([first, first + 2, first + 1, first, first + 3, first + 2]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
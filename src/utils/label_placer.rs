@@ -43,7 +43,7 @@ impl Plugin for LabelPlacerPlugin {
 fn label_placer(
     windows: Res<Windows>,
     mut label_query: Query<
-        (&mut Style, &CalculatedSize, &Label, &ComputedVisibility)
+        (&mut Style, &CalculatedSize, &mut Label, &ComputedVisibility)
     >,
     lb_query: Query<(&Transform, &ComputedVisibility), (With<LabelBase>)>,
     camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
@@ -52,9 +52,14 @@ fn label_placer(
 ) // Main 3d camera needs struct "MainCamera"
 {
     // let window = windows.get_primary().unwrap();
-    if ev_cameramoved.iter().next().is_some() {
+    // Labels spawned since the last move are placed as soon as they are visible
+    let moved = ev_cameramoved.iter().next().is_some();
+    if moved || label_query.iter().any(|(_, _, label, _)| !label.placed) {
         for (camera, camera_transform) in camera_query.iter() {
-            for (mut style, calculated, label, cv) in label_query.iter_mut() {
+            for (mut style, calculated, mut label, cv) in label_query.iter_mut() {
+                if !moved && label.placed {
+                    continue
+                }
                 if !cv.is_visible() {
                     continue
                 }
@@ -74,6 +79,7 @@ fn label_placer(
                         style.position.bottom = Val::Px(-1000.0);
                     }
                 }
+                label.placed = true;
             }
         }
     }
@@ -1,3 +1,9 @@
+pub mod glyphs;
 pub mod label_placer;
+pub mod ruler;
+pub mod viewport;
 
+pub use glyphs::*;
 pub use label_placer::*;
+pub use ruler::*;
+pub use viewport::*;
@@ -0,0 +1,83 @@
+/// Tick spacing in bp, 1, 2 or 5 x 10^n, giving at most max_ticks ticks over span bp
+pub fn tick_interval(span: f32, max_ticks: usize) -> usize {
+    let target = (span / max_ticks.max(1) as f32).max(1.0);
+    let magnitude = 10f32.powi(target.log10().floor() as i32);
+
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|x| x * magnitude)
+        .find(|x| *x >= target)
+        .unwrap_or(magnitude * 10.0);
+
+    (step.round() as usize).max(1)
+}
+
+/// Position as bp, kb or Mb, with as many decimals as the tick interval needs to tell
+/// neighbouring ticks apart: 1,500 bp, 12.5 kb, 3 Mb
+pub fn format_position(position: usize, interval: usize) -> String {
+    let (unit, name) = if interval >= 1_000_000 || (position >= 1_000_000 && interval >= 100_000) {
+        (1_000_000, "Mb")
+    } else if interval >= 1_000 || (position >= 10_000 && interval >= 100) {
+        (1_000, "kb")
+    } else {
+        return format!("{} bp", group_thousands(position));
+    };
+
+    // Digits needed to show the interval in this unit
+    let mut decimals = 0;
+    let mut x = interval;
+    while x % unit != 0 && decimals < 6 {
+        x *= 10;
+        decimals += 1;
+    }
+
+    format!("{:.*} {}", decimals, position as f64 / unit as f64, name)
+}
+
+fn group_thousands(x: usize) -> String {
+    let digits = x.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (n, c) in digits.chars().enumerate() {
+        if n > 0 && (digits.len() - n) % 3 == 0 {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_interval() {
+        assert_eq!(tick_interval(1000.0, 10), 100);
+        assert_eq!(tick_interval(1500.0, 10), 200);
+        assert_eq!(tick_interval(3000.0, 10), 500);
+        assert_eq!(tick_interval(7000.0, 10), 1000);
+        assert_eq!(tick_interval(2_500_000.0, 8), 500_000);
+
+        // At least one tick, and never under 1 bp
+        assert_eq!(tick_interval(1500.0, 0), 2000);
+        assert_eq!(tick_interval(5.0, 10), 1);
+        assert_eq!(tick_interval(0.0, 10), 1);
+    }
+
+    #[test]
+    fn test_format_position() {
+        assert_eq!(format_position(1500, 100), "1,500 bp");
+        assert_eq!(format_position(12_500, 500), "12.5 kb");
+        assert_eq!(format_position(12_000, 1000), "12 kb");
+        assert_eq!(format_position(12_000, 500), "12.0 kb");
+        assert_eq!(format_position(9_500, 500), "9,500 bp");
+        assert_eq!(format_position(12_020, 20), "12,020 bp");
+        assert_eq!(format_position(3_000_000, 1_000_000), "3 Mb");
+        assert_eq!(format_position(3_500_000, 500_000), "3.5 Mb");
+        assert_eq!(format_position(1_250_000, 50_000), "1250 kb");
+
+        assert_eq!(group_thousands(0), "0");
+        assert_eq!(group_thousands(999), "999");
+        assert_eq!(group_thousands(1_234_567), "1,234,567");
+    }
+}
@@ -0,0 +1,49 @@
+use bevy::prelude::*;
+use bevy::render::camera::*;
+
+/// World x coordinates (bp in the sequence view) seen by a camera on the z = 0 plane, the
+/// sequence view is drawn there. Takes the camera's Transform rather than its GlobalTransform, it
+/// has no parent and the GlobalTransform lags a frame behind camera moves.
+pub fn visible_range(projection: &Projection, transform: &Transform) -> (f32, f32) {
+    let scale = transform.scale;
+    let translation = transform.translation;
+
+    let half_width = match projection {
+        Projection::Perspective(x) => translation.z.abs() * (x.fov / 2.0).tan() * x.aspect_ratio,
+        Projection::Orthographic(x) => (x.right - x.left).abs() / 2.0 * x.scale,
+    } * scale.x;
+
+    (translation.x - half_width, translation.x + half_width)
+}
+
+/// Same, clamped to [0, length) and widened by margin (a fraction of the visible width) on both
+/// sides, so entities are ready before they scroll in
+pub fn visible_bases(
+    projection: &Projection,
+    transform: &Transform,
+    margin: f32,
+    length: usize,
+) -> (usize, usize) {
+    let (start, end) = visible_range(projection, transform);
+    let margin = (end - start) * margin;
+
+    let start = (start - margin).max(0.0) as usize;
+    let end = ((end + margin).max(0.0) as usize).min(length);
+    (start, end)
+}
+
+/// Centers the camera on [start, end) and scales it so that is exactly the visible range. Returns
+/// the new x scale.
+pub fn frame_range(
+    projection: &Projection,
+    transform: &mut Transform,
+    start: f32,
+    end: f32,
+) -> f32 {
+    let unscaled = Transform::from_translation(transform.translation);
+    let (from, to) = visible_range(projection, &unscaled);
+
+    transform.translation.x = (start + end) / 2.0;
+    transform.scale.x = ((end - start) / (to - from)).max(f32::EPSILON);
+    transform.scale.x
+}
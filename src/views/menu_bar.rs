@@ -1,29 +1,416 @@
-use bevy::prelude::*;
-use bevy_egui::{egui, EguiContext, EguiPlugin, EguiSettings};
-
-use crate::core::states::*;
-
-pub struct MenuBarPlugin;
-impl Plugin for MenuBarPlugin {
-    fn build(&self, app: &mut App) {
-        app.add_system(ui_example);
-    }
-}
-
-fn ui_example(mut egui_ctx: ResMut<EguiContext>) {
-    egui::TopBottomPanel::top("top_panel").show(egui_ctx.ctx_mut(), |ui| {
-        egui::menu::bar(ui, |ui| {
-            ui.menu_button("File", |ui| {
-                if ui.button("Quit").clicked() {
-                    std::process::exit(0);
-                }
-            });
-        });
-    });
-}
-
-fn menu_buttons(mut commands: Commands) {}
-
-fn setup_menu(mut commands: Commands) {}
-
-fn close_menu(mut commands: Commands) {}
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext, EguiPlugin, EguiSettings};
+use crossbeam::channel::{unbounded, Receiver, Sender};
+
+use std::sync::Arc;
+
+use crate::core::states::*;
+use crate::loading::{FileLoaded, Loading, RecentFiles};
+use crate::parsers::*;
+use crate::structs::*;
+
+pub struct MenuBarPlugin;
+impl Plugin for MenuBarPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LocusInput>()
+            .init_resource::<OpenPath>()
+            .init_resource::<SearchIndexTasks>()
+            .add_system(start_search_index)
+            .add_system(receive_search_index)
+            .add_system(go_to_pending.after("poll_loading"))
+            .add_system(ui_example.label("menu_bar"));
+    }
+}
+
+// Bases shown around a single position typed in the locus box
+const POSITION_CONTEXT: usize = 100;
+
+// Suggestions shown under the locus box
+const SUGGESTIONS: usize = 10;
+
+// Each loaded file is indexed off the main thread, then merged into the SearchIndex resource
+struct SearchIndexTasks {
+    pending: usize,
+    sender: Sender<SearchIndex>,
+    receiver: Receiver<SearchIndex>,
+}
+
+impl Default for SearchIndexTasks {
+    fn default() -> Self {
+        let (sender, receiver) = unbounded();
+        SearchIndexTasks {
+            pending: 0,
+            sender,
+            receiver,
+        }
+    }
+}
+
+// Only the file that just loaded, what's already indexed stays as it is
+fn start_search_index(
+    mut ev_loaded: EventReader<FileLoaded>,
+    mut tasks: ResMut<SearchIndexTasks>,
+    bstate: Res<BrowserState>,
+) {
+    for loaded in ev_loaded.iter() {
+        let sources = bstate
+            .tracks
+            .iter()
+            .filter(|x| x.source.filename() == loaded.filename)
+            .map(|x| x.source.clone())
+            .collect::<Vec<Arc<TrackSource>>>();
+        let segments = bstate
+            .gfa
+            .as_ref()
+            .filter(|x| x.filename == loaded.filename)
+            .map(|x| {
+                x.lengths
+                    .iter()
+                    .map(|(id, length)| (id.clone(), *length))
+                    .collect::<Vec<(String, usize)>>()
+            })
+            .unwrap_or_default();
+
+        if sources.is_empty() && segments.is_empty() {
+            continue;
+        }
+
+        tasks.pending += 1;
+        let sender = tasks.sender.clone();
+        std::thread::spawn(move || {
+            let mut index = SearchIndex::new();
+
+            for source in sources.iter() {
+                for landmark in source.landmarks() {
+                    match source.parse_region(&landmark.id) {
+                        Ok(x) => index.add_features(&x),
+                        Err(err) => println!("Unable to index {}: {}", landmark.id, err),
+                    }
+                }
+            }
+
+            for (id, length) in segments {
+                index.add_segment(&id, length);
+            }
+
+            index.finish();
+            // Only fails if the app is gone
+            let _ = sender.send(index);
+        });
+    }
+}
+
+fn receive_search_index(
+    mut commands: Commands,
+    mut tasks: ResMut<SearchIndexTasks>,
+    search: Option<ResMut<SearchIndex>>,
+    mut locus: ResMut<LocusInput>,
+) {
+    let mut found = tasks.receiver.try_iter().collect::<Vec<SearchIndex>>();
+    if found.is_empty() {
+        return;
+    }
+    tasks.pending -= found.len();
+
+    let mut merged = match search {
+        Some(mut x) => std::mem::take(&mut *x),
+        None => found.remove(0),
+    };
+    for index in found {
+        merged.merge(index);
+    }
+
+    commands.insert_resource(merged);
+    // Suggestions are looked up again with the new names
+    locus.query.clear();
+}
+
+#[derive(Default)]
+pub struct LocusInput {
+    text: String,
+    error: Option<String>,
+    pending: bool, // Gone to once everything is loaded, for --locus
+    query: String, // What the suggestions are for, they're only searched for when text changes
+    suggestions: Vec<SearchEntry>,
+}
+
+impl LocusInput {
+    pub fn pending(text: &str) -> Self {
+        LocusInput {
+            text: text.to_string(),
+            pending: true,
+            ..Default::default()
+        }
+    }
+}
+
+fn go_to_pending(
+    mut locus: ResMut<LocusInput>,
+    loading: Res<Loading>,
+    mut ev_loaded: EventReader<FileLoaded>,
+    tasks: Res<SearchIndexTasks>,
+    search: Option<Res<SearchIndex>>,
+    mut bstate: ResMut<BrowserState>,
+    mut state: ResMut<State<AppState>>,
+    mut ev_load: EventWriter<LoadLandmark>,
+    mut ev_frame: EventWriter<FrameRange>,
+) {
+    // Files that just finished start indexing this frame, feature names can wait for it
+    if !locus.pending || !loading.is_empty() || ev_loaded.iter().count() > 0 || tasks.pending > 0 {
+        return;
+    }
+
+    locus.pending = false;
+    match go_to(&locus.text, &bstate, search.as_deref()) {
+        Ok(target) => open(target, &mut bstate, &mut state, &mut ev_load, &mut ev_frame),
+        Err(err) => locus.error = Some(err),
+    }
+}
+
+// File > Open path..., works without a native file dialog
+#[derive(Default)]
+pub struct OpenPath {
+    open: bool,
+    text: String,
+    error: Option<String>,
+}
+
+impl OpenPath {
+    pub fn show(&mut self) {
+        self.open = true;
+    }
+}
+
+#[cfg(feature = "rfd")]
+fn pick_files() -> Vec<String> {
+    rfd::FileDialog::new()
+        .pick_files()
+        .unwrap_or_default()
+        .iter()
+        .map(|x| x.to_string_lossy().to_string())
+        .collect()
+}
+
+fn ui_example(
+    mut egui_ctx: ResMut<EguiContext>,
+    mut loading: ResMut<Loading>,
+    recent: Res<RecentFiles>,
+    mut open_path: ResMut<OpenPath>,
+    mut locus: ResMut<LocusInput>,
+    mut bstate: ResMut<BrowserState>,
+    mut state: ResMut<State<AppState>>,
+    mut ev_load: EventWriter<LoadLandmark>,
+    mut ev_frame: EventWriter<FrameRange>,
+    search: Option<Res<SearchIndex>>,
+) {
+    egui::TopBottomPanel::top("top_panel").show(egui_ctx.ctx_mut(), |ui| {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button("File", |ui| {
+                let mut chosen = Vec::new();
+
+                #[cfg(feature = "rfd")]
+                if ui.button("Open...").clicked() {
+                    ui.close_menu();
+                    chosen = pick_files();
+                }
+
+                if ui.button("Open path...").clicked() {
+                    ui.close_menu();
+                    open_path.open = true;
+                }
+
+                ui.menu_button("Recent", |ui| {
+                    if recent.files.is_empty() {
+                        ui.label("Nothing opened yet");
+                    }
+                    for x in recent.files.iter() {
+                        if ui.button(x).clicked() {
+                            ui.close_menu();
+                            chosen.push(x.clone());
+                        }
+                    }
+                });
+
+                // Errors go in the path window, it's the one place to retry from
+                for x in chosen {
+                    if let Err(err) = loading.open(&x) {
+                        open_path.text = x;
+                        open_path.error = Some(err);
+                        open_path.open = true;
+                    }
+                }
+
+                ui.separator();
+
+                if ui.button("Quit").clicked() {
+                    std::process::exit(0);
+                }
+            });
+
+            ui.separator();
+
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut locus.text)
+                    .hint_text("chr1:10,000-20,000")
+                    .desired_width(240.0),
+            );
+            let entered = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+
+            let mut target = None;
+
+            // Autocomplete from feature names and segment IDs
+            let popup_id = ui.make_persistent_id("locus_suggestions");
+            if response.changed() {
+                ui.memory().open_popup(popup_id);
+            }
+            let popup_open = ui.memory().is_popup_open(popup_id);
+            if let (true, Some(search)) = (popup_open, &search) {
+                if locus.query != locus.text {
+                    locus.suggestions = search
+                        .search(&locus.text, SUGGESTIONS)
+                        .into_iter()
+                        .cloned()
+                        .collect();
+                    locus.query = locus.text.clone();
+                }
+                if locus.suggestions.is_empty() {
+                    ui.memory().close_popup();
+                }
+
+                let suggestions = locus.suggestions.clone();
+                egui::popup::popup_below_widget(ui, popup_id, &response, |ui| {
+                    for x in suggestions {
+                        let text = format!(
+                            "{} ({}, {}:{}-{})",
+                            x.name,
+                            x.feature_type,
+                            x.landmark,
+                            x.start + 1,
+                            x.end
+                        );
+                        if ui.selectable_label(false, text).clicked() {
+                            target = bstate
+                                .landmark_length(&x.landmark)
+                                .map(|length| (x.landmark.clone(), length, (x.start, x.end)));
+                            locus.text = x.name.clone();
+                        }
+                    }
+                });
+            }
+
+            if ui.button("Go").clicked() || entered {
+                match go_to(&locus.text, &bstate, search.as_deref()) {
+                    Ok(x) => target = Some(x),
+                    Err(err) => locus.error = Some(err),
+                }
+            }
+
+            if let Some(target) = target {
+                locus.error = None;
+                open(target, &mut bstate, &mut state, &mut ev_load, &mut ev_frame);
+            }
+
+            if let Some(err) = &locus.error {
+                ui.colored_label(egui::Color32::LIGHT_RED, err);
+            }
+        });
+    });
+
+    let mut open = open_path.open;
+    let mut opened = false;
+    egui::Window::new("Open")
+        .open(&mut open)
+        .collapsible(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.label("GFA, GFF3, GTF, FASTA, BED or VCF, plain or gzipped");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut open_path.text)
+                    .hint_text("path/to/file")
+                    .desired_width(320.0),
+            );
+            let entered = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+
+            if ui.button("Open").clicked() || entered {
+                match loading.open(open_path.text.trim()) {
+                    Ok(()) => {
+                        open_path.text.clear();
+                        open_path.error = None;
+                        opened = true;
+                    }
+                    Err(err) => open_path.error = Some(err),
+                }
+            }
+
+            if let Some(err) = &open_path.error {
+                ui.colored_label(egui::Color32::LIGHT_RED, err);
+            }
+        });
+    open_path.open = open && !opened;
+}
+
+// Opens the sequence view on the landmark, unless it's already there, and frames the range
+fn open(
+    (landmark, length, (start, end)): (String, usize, (usize, usize)),
+    bstate: &mut BrowserState,
+    state: &mut State<AppState>,
+    ev_load: &mut EventWriter<LoadLandmark>,
+    ev_frame: &mut EventWriter<FrameRange>,
+) {
+    let reload = bstate.landmark.as_ref().map(|x| &x.0) != Some(&landmark);
+    bstate.landmark = Some((landmark.clone(), length));
+
+    if reload {
+        ev_load.send(LoadLandmark { id: landmark });
+        let changed = if *state.current() == AppState::SequenceView {
+            state.restart()
+        } else {
+            state.replace(AppState::SequenceView)
+        };
+        if let Err(err) = changed {
+            println!("Unable to open the sequence view: {:?}", err);
+        }
+    }
+
+    ev_frame.send(FrameRange { start, end });
+}
+
+// Landmark, its length and the 0-based range to show
+fn go_to(
+    text: &str,
+    bstate: &BrowserState,
+    search: Option<&SearchIndex>,
+) -> Result<(String, usize, (usize, usize)), String> {
+    // A landmark whose name looks like it has coordinates
+    let text = text.trim();
+    if let Some(length) = bstate.landmark_length(text) {
+        return Ok((text.to_string(), length, (0, length)));
+    }
+
+    // Exactly a feature name
+    let feature = search
+        .and_then(|x| x.search(text, 1).into_iter().next())
+        .filter(|x| x.name.eq_ignore_ascii_case(text));
+    if let Some(x) = feature {
+        if let Some(length) = bstate.landmark_length(&x.landmark) {
+            return Ok((x.landmark.clone(), length, (x.start, x.end)));
+        }
+    }
+
+    let locus = text.parse::<Locus>().map_err(|x| x.to_string())?;
+    match bstate.landmark_length(&locus.landmark) {
+        Some(length) => {
+            let range = locus.range(length, POSITION_CONTEXT);
+            if range.0 >= range.1 {
+                return Err(format!("{} is past the end of {}", locus, locus.landmark));
+            }
+            Ok((locus.landmark, length, range))
+        }
+        None => Err(format!("unknown landmark {}", locus.landmark)),
+    }
+}
+
+fn menu_buttons(mut commands: Commands) {}
+
+fn setup_menu(mut commands: Commands) {}
+
+fn close_menu(mut commands: Commands) {}
@@ -2,8 +2,12 @@ pub mod main_menu;
 pub mod menu_bar;
 pub mod sequence_overview;
 pub mod sequence_view;
+pub mod track_panel;
+pub mod variant_inspector;
 
 pub use main_menu::MainMenuPlugin;
 pub use menu_bar::MenuBarPlugin;
 pub use sequence_overview::SequenceOverviewPlugin;
 pub use sequence_view::*;
+pub use track_panel::TrackPanelPlugin;
+pub use variant_inspector::VariantInspectorPlugin;
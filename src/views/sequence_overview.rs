@@ -70,9 +70,10 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     asset_server: Res<AssetServer>,
     camera_query: Query<(&Camera, &GlobalTransform), With<Camera>>,
-    gff3: Option<Res<Gff3>>,
-    gfa: Option<Res<Gfa>>,
+    bstate: Res<BrowserState>,
 ) {
+    let gfa = bstate.gfa.as_ref();
+
     let font = asset_server.load("fonts/FiraSans-Bold.ttf");
     let text_style = TextStyle {
         font,
@@ -82,14 +83,37 @@ fn setup(
 
     let text_alignment = TextAlignment::default();
 
-    if gff3.is_none() && gfa.is_none() {
+    if bstate.tracks.is_empty() && gfa.is_none() && bstate.fasta.is_none() {
         return;
     }
 
-    if gff3.is_some() {
-        let genome = gff3.unwrap();
+    // The first track's landmarks, the rest usually annotate the same assembly
+    let annotations = bstate.tracks.first().map(|x| x.source.landmarks());
+
+    // Annotations only give the largest coordinate seen, the FASTA has the real lengths
+    let fasta = bstate.fasta.as_ref();
+    let landmarks: Option<Vec<(String, usize)>> = match (annotations, fasta) {
+        (Some(annotations), _) => Some(
+            annotations
+                .iter()
+                .map(|x| {
+                    let length = fasta.and_then(|f| f.length(&x.id)).unwrap_or(x.length);
+                    (x.id.clone(), length)
+                })
+                .collect(),
+        ),
+        (None, Some(fasta)) => Some(
+            fasta
+                .index
+                .iter()
+                .map(|x| (x.name.clone(), x.length))
+                .collect(),
+        ),
+        (None, None) => None,
+    };
 
-        for (i, landmark) in genome.landmarks.iter().enumerate() {
+    if let Some(landmarks) = landmarks {
+        for (i, (landmark, length)) in landmarks.iter().enumerate() {
             // 5 per row
             let row = i / 5;
             let col = i % 5;
@@ -121,13 +145,13 @@ fn setup(
                 .insert_bundle(PickableBundle::default())
                 .insert(LabelBase)
                 .insert(SequenceOverviewItem)
-                .insert(ClickableLandmark::from(&landmark.0, landmark.3))
+                .insert(ClickableLandmark::from(landmark, *length))
                 .id();
 
             commands
                 .spawn_bundle(TextBundle {
                     text: Text::from_section(
-                        landmark.0.to_string(),
+                        landmark.to_string(),
                         text_style.clone(),
                     ).with_alignment(text_alignment),
                     style: Style {
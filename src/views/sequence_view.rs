@@ -3,13 +3,18 @@ use bevy::prelude::*;
 use bevy::render::camera::*;
 use bevy_egui::{egui, EguiContext, EguiPlugin, EguiSettings};
 use bevy_mod_picking::*;
-use rand::prelude::*;
-use rand_xoshiro::SplitMix64;
 use rayon::prelude::*;
 
+use crossbeam::channel::{unbounded, Receiver, Sender};
+
+use std::collections::HashSet;
+
 use crate::core::states::*;
 use crate::structs::*;
+use crate::utils::glyphs::*;
 use crate::utils::label_placer::*;
+use crate::utils::ruler::*;
+use crate::utils::viewport::*;
 use crate::*;
 
 #[derive(Component)]
@@ -23,6 +28,8 @@ impl Plugin for SequenceViewPlugin {
         //                .with_system(draw_primary.system()),
         //        )
 
+        app.init_resource::<GlyphAssets>();
+
         app.add_system_set(
             SystemSet::on_enter(AppState::SequenceView)
                 .with_system(setup)
@@ -32,10 +39,21 @@ impl Plugin for SequenceViewPlugin {
         .add_system_set(SystemSet::on_exit(AppState::SequenceView).with_system(cleanup))
         .add_system_set(
             SystemSet::on_update(AppState::SequenceView)
+                .with_system(frame_requested_range)
+                .with_system(index_tracks)
+                .with_system(relayout_tracks)
+                .with_system(draw_ticks)
+                .with_system(stream_features)
+                .with_system(stream_segments)
+                .with_system(draw_density)
+                .with_system(draw_signal)
+                .with_system(draw_bases)
                 .with_system(check_links)
                 .with_system(draw_feature)
-                .with_system(draw_lines)
-                .with_system(collision_check),
+                .with_system(rescale_chevrons)
+                .with_system(draw_variant)
+                .with_system(rescale_variants)
+                .with_system(draw_lines),
         );
     }
 }
@@ -47,52 +65,16 @@ enum MoveDirection {
     Down,
 }
 
-fn collision_check(
-    mut q0: Query<(&mut Transform, &Collider, Entity)>,
-) {
-    let mut rng = SplitMix64::from_rng(thread_rng()).unwrap();
-
-    let mut iter = q0.iter_combinations_mut();
-
-    while let Some([(transform_a, collider_a, entity_a), (mut transform_b, collider_b, entity_b)]) = iter.fetch_next() {
-
-        // https://github.com/bevyengine/bevy/blob/c5717b5a9124c7c2f7431c4be07f15243ebd60b5/crates/bevy_sprite/src/collide_aabb.rs
-        let a_min = transform_a.translation.truncate() - collider_a.size / 2.0;
-        let a_max = transform_a.translation.truncate() + collider_a.size / 2.0;
-
-        let b_min = transform_b.translation.truncate() - collider_b.size / 2.0;
-        let b_max = transform_b.translation.truncate() + collider_b.size / 2.0;
-
-        if a_min.x < b_max.x && a_max.x > b_min.x && a_min.y < b_max.y && a_max.y > b_min.y {
-            // println!("Collision detected between {:#?} and {:#?}\n{:#?}\n{:#?}\n{:#?}\n{:#?}", entity_a, entity_b, a_min, a_max, b_min, b_max);
-
-            // Top left corner is min.x and max.y
-            // Bottom left corner is min.x and min.y
-            // Top right corner is max.x and max.y
-            // Bottom right corner is max.x and min.y
-
-            let amt_y = rng.gen_range(-0.1..0.1);
-            let amt_x = rng.gen_range(-10000.0..10000.0);
-   
-            if rng.gen::<bool>() {
-                transform_b.translation.x -= amt_x;
-            }
-            if rng.gen::<bool>() {
-                transform_b.translation.y -= amt_y;
-            }
-        }
-    }
-}
-
 fn setup(
     mut commands: Commands,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    mut glyphs: ResMut<GlyphAssets>,
     bstate: Res<BrowserState>,
     mut registry: ResMut<EntityRegistry>,
     asset_server: Res<AssetServer>,
     mut camera_query: Query<(&Camera, &mut Transform), With<MainCamera>>,
     mut ui_setting: ResMut<UISetting>,
+    mut ev_cameramoved: EventWriter<CameraMoved>,
 ) {
     // Draw 3d chromosome on the main camera (could be another, for example if only looking at a gene, or something)
 
@@ -102,20 +84,12 @@ fn setup(
 
     let id = commands
         .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Quad {
-                size: Vec2::new(length as f32, 0.4),
-                flip: false,
-            })),
-            material: materials.add(StandardMaterial {
-                base_color: Color::BISQUE,
-                // emissive: Color::WHITE * 10.0f32,
-                ..Default::default()
-            }),
-            transform: Transform {
-                rotation: Quat::from_euler(EulerRot::XYZ, 0., 0., 0.), // std::f32::consts::FRAC_PI_2), // 1.5708),
-                translation: Vec3::new(length as f32 / 2.0, 0., 0.),
-                scale: Vec3::new(1., 1., 1.),
-            },
+            mesh: glyphs.quad.clone(),
+            material: glyphs.material(&mut materials, Color::BISQUE),
+            transform: rectangle(
+                Vec3::new(length as f32 / 2.0, 0., 0.),
+                Vec2::new(length as f32, 0.4),
+            ),
 
             //        material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
             //        material: materials.add(StandardMaterial {
@@ -139,39 +113,7 @@ fn setup(
 
     registry.registry.insert(landmark, id);
 
-    // Draw vertical lines every 10kbp bases...
-    // TODO: But only in visible region
-    //for i in (0..length).step_by(100) {
-    for i in (0..length).step_by(10000).take(10) {
-        let x = i as f32;
-        commands
-            .spawn_bundle(PbrBundle {
-                mesh: meshes.add(Mesh::from(shape::Quad {
-                    size: Vec2::new(0.1, 5.5),
-                    flip: false,
-                })),
-                material: materials.add(StandardMaterial {
-                    base_color: Color::WHITE,
-                    ..Default::default()
-                }),
-                transform: Transform {
-                    rotation: Quat::from_euler(EulerRot::XYZ, 0., 0., 0.), // std::f32::consts::FRAC_PI_2), // 1.5708),
-                    translation: Vec3::new(x, 0., 0.),
-                    scale: Vec3::new(1., 1., 1.),
-                },
-
-                //        material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
-                //        material: materials.add(StandardMaterial {
-                //            base_color: Color::hex("ffd891").unwrap(),
-                // vary key PBR parameters on a grid of spheres to show the effect
-                //            ..Default::default()
-                //        }),
-                //        transform: Transform::from_xyz(-5.0, -2.5, 0.0),
-                ..Default::default()
-            })
-            .insert(Name::from("Tick"))
-            .id();
-    }
+    // Ticks are drawn by draw_ticks, following the camera
 
     let (camera, mut transform) = camera_query.single_mut();
 
@@ -185,100 +127,1100 @@ fn setup(
         .transform_point3(Vec3::new(length as f32 / 2., 0., 0.));
     let scale = x.x / 15.;
     transform.scale.x = scale;
-    ui_setting.zoom_factor += scale;
+    ui_setting.zoom_factor = 1.0 + scale;
     // transform.translation.x -= length as f32 / 2.0;
+
+    // Streams in what is visible
+    ev_cameramoved.send(CameraMoved);
 }
 
+// From the locus box
+fn frame_requested_range(
+    mut ev_frame: EventReader<FrameRange>,
+    mut ev_cameramoved: EventWriter<CameraMoved>,
+    mut ui_setting: ResMut<UISetting>,
+    mut camera: Query<(&Projection, &mut Transform), With<MainCamera>>,
+) {
+    let range = match ev_frame.iter().last() {
+        Some(x) => x,
+        None => return,
+    };
+
+    let (projection, mut transform) = camera.single_mut();
+    let scale = frame_range(
+        projection,
+        &mut transform,
+        range.start as f32,
+        range.end.max(range.start + 1) as f32,
+    );
+
+    // Same relation to the camera scale as in setup
+    ui_setting.zoom_factor = 1.0 + scale;
+    ev_cameramoved.send(CameraMoved);
+}
+
+#[derive(Component)]
+pub struct RulerTick;
+
+const MAX_TICKS: usize = 10;
+
+// Ruler over the visible span, redrawn whenever the camera moves
 fn draw_ticks(
     mut commands: Commands,
-    windows: Res<Windows>,
-    mut camera_query: Query<(&Camera, &mut Transform), With<MainCamera>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut glyphs: ResMut<GlyphAssets>,
+    mut ev_cameramoved: EventReader<CameraMoved>,
+    asset_server: Res<AssetServer>,
+    bstate: Res<BrowserState>,
+    camera: Query<(&Projection, &Transform), With<MainCamera>>,
+    ticks: Query<Entity, With<RulerTick>>,
 ) {
-    let (camera, mut transform) = camera_query.single_mut();
-    if let RenderTarget::Window(window_id) = camera.target {
-        let window = windows.get(window_id).unwrap();
+    if ev_cameramoved.iter().count() == 0 {
+        return;
+    }
+
+    for e in ticks.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+
+    let length = match &bstate.landmark {
+        Some((_, length)) => *length,
+        None => return,
+    };
+
+    let (start, end) = camera_bases(&camera, 0.0, length);
+    if start >= end {
+        return;
     }
 
-    // Have to calculate viewable space
-    // Update at the start, then update when zoom in/out
+    let interval = tick_interval((end - start) as f32, MAX_TICKS);
+    // Thin enough to look like a line at any zoom
+    let width = (end - start) as f32 / 1000.0;
+
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 12.,
+        color: Color::WHITE,
+    };
+
+    let first = (start + interval - 1) / interval * interval;
+    for position in (first..=end).step_by(interval) {
+        let id = commands
+            .spawn_bundle(PbrBundle {
+                mesh: glyphs.quad.clone(),
+                material: glyphs.material(&mut materials, Color::WHITE),
+                transform: rectangle(
+                    Vec3::new(position as f32, 2.0, 0.),
+                    Vec2::new(width, 0.5),
+                ),
+                ..Default::default()
+            })
+            .insert(Name::from("Tick"))
+            .insert(LabelBase)
+            .insert(RulerTick)
+            .insert(SequenceViewItem)
+            .id();
 
-    // TODO: Move to a less-frequently run system
+        commands
+            .spawn_bundle(TextBundle {
+                text: Text::from_section(
+                    format_position(position, interval),
+                    text_style.clone(),
+                ),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Label::belongs_to(id).with_offset(Vec3::new(0., 14.0, 0.)))
+            .insert(RulerTick)
+            .insert(SequenceViewItem);
+    }
 }
 
 fn draw_primary(mut commands: Commands) {}
 
+// Extra width, as a fraction of the visible range, spawned on either side
+const VISIBLE_MARGIN: f32 = 0.25;
+
 fn create_gff3_entities(mut commands: Commands, bstate: Res<BrowserState>) {
     if bstate.landmark.is_none() {
         println!("bstate landmark is none");
         return;
     }
 
-    if bstate.gff3.is_none() {
-        println!("bstate gff3 is none");
+    let (landmark, _) = bstate.landmark.clone().unwrap();
+
+    // Filled in by index_tracks, tracks can be added while the view is open
+    let (sender, receiver) = unbounded();
+    commands.insert_resource(TrackIndices {
+        landmark,
+        indices: Vec::new(),
+        pending: HashSet::new(),
+        sender,
+        receiver,
+    });
+}
+
+// Bases kept between features packed in the same row
+const ROW_GAP: usize = 10;
+
+// Collapsed glyphs, packed ones take half their row
+const GLYPH_HEIGHT: f32 = 0.2;
+
+// One FeatureIndex per track with features on the landmark
+struct TrackIndices {
+    landmark: String,
+    indices: Vec<FeatureIndex>,
+    pending: HashSet<usize>, // Tracks being parsed
+    sender: Sender<FeatureIndex>,
+    receiver: Receiver<FeatureIndex>,
+}
+
+// Parses new tracks off the main thread, picks up the ones done and drops the ones removed
+fn index_tracks(
+    mut commands: Commands,
+    indices: Option<ResMut<TrackIndices>>,
+    mut bstate: ResMut<BrowserState>,
+    mut ev_tracks: EventWriter<TracksChanged>,
+) {
+    let mut indices = match indices {
+        Some(x) => x,
+        None => return,
+    };
+    let indices = &mut *indices;
+
+    for track in bstate.tracks.iter() {
+        if indices.pending.contains(&track.id) || indices.indices.iter().any(|x| x.track == track.id)
+        {
+            continue;
+        }
+
+        indices.pending.insert(track.id);
+
+        let id = track.id;
+        let source = track.source.clone();
+        let landmark = indices.landmark.clone();
+        let sender = indices.sender.clone();
+        std::thread::spawn(move || {
+            let index = match &*source {
+                TrackSource::Vcf(vcf) => index_variants(vcf, &landmark, id),
+                _ => index_features(&source, &landmark, id),
+            };
+
+            match index {
+                // Only fails if the view was left in the meantime
+                Ok(x) => {
+                    let _ = sender.send(x);
+                }
+                Err(err) => println!("Unable to load features for {}: {}", landmark, err),
+            }
+        });
+    }
+
+    let mut changed = false;
+
+    for index in indices.receiver.try_iter() {
+        indices.pending.remove(&index.track);
+        // Packed rows make the lane taller
+        if let Some(track) = bstate.tracks.iter_mut().find(|x| x.id == index.track) {
+            track.rows = index.rows;
+            indices.indices.push(index);
+            changed = true;
+        }
+    }
+
+    indices.indices.retain(|x| {
+        if bstate.track(x.track).is_some() {
+            return true;
+        }
+        for e in x.spawned.values() {
+            commands.entity(*e).despawn_recursive();
+        }
+        changed = true;
+        false
+    });
+
+    // Entities are spawned by stream_features as they come into view, in lanes laid out again
+    if changed {
+        ev_tracks.send(TracksChanged);
+    }
+}
+
+fn index_features(
+    source: &TrackSource,
+    landmark: &str,
+    track: usize,
+) -> Result<FeatureIndex, ParseError> {
+    // Drawn a transcript at a time, their exons and CDS hang off of them as subfeatures
+    let features = transcripts(source.parse_region(landmark)?);
+
+    let mut features = IntervalIndex::from_features(features);
+    let rows = features.pack_features(ROW_GAP);
+
+    Ok(FeatureIndex {
+        landmark: landmark.to_string(),
+        track,
+        features,
+        rows,
+        ..Default::default()
+    })
+}
+
+// Sites take the first row, packed and expanded modes give each sample a row under them
+fn index_variants(vcf: &Vcf, landmark: &str, track: usize) -> Result<FeatureIndex, ParseError> {
+    let variants = vcf
+        .parse_region(landmark)?
+        .into_iter()
+        .map(|x| (x.position.saturating_sub(1), x.end(), x))
+        .collect::<Vec<(usize, usize, Variant)>>();
+
+    Ok(FeatureIndex {
+        landmark: landmark.to_string(),
+        track,
+        variants: IntervalIndex::new(variants),
+        rows: 1 + vcf.header.samples.len(),
+        ..Default::default()
+    })
+}
+
+// Lanes moved, everything is spawned again in its new place
+fn relayout_tracks(
+    mut commands: Commands,
+    mut ev_tracks: EventReader<TracksChanged>,
+    indices: Option<ResMut<TrackIndices>>,
+    mut ev_cameramoved: EventWriter<CameraMoved>,
+) {
+    if ev_tracks.iter().count() == 0 {
         return;
     }
 
-    let (landmark, length) = bstate.landmark.clone().unwrap();
-    let features = bstate
-        .gff3
-        .as_ref()
-        .unwrap()
-        .parse_region(&landmark)
-        .unwrap();
+    if let Some(mut indices) = indices {
+        for index in indices.indices.iter_mut() {
+            for (_, e) in index.spawned.drain() {
+                commands.entity(e).despawn_recursive();
+            }
+        }
+    }
+
+    ev_cameramoved.send(CameraMoved);
+}
+
+// Visible range of the main camera, widened by margin, in bp
+fn camera_bases(
+    camera: &Query<(&Projection, &Transform), With<MainCamera>>,
+    margin: f32,
+    length: usize,
+) -> (usize, usize) {
+    match camera.get_single() {
+        Ok((projection, transform)) => visible_bases(projection, transform, margin, length),
+        Err(_) => (0, length),
+    }
+}
+
+// Of what the main camera shows
+fn detail_level(
+    camera: &Query<(&Projection, &Transform), With<MainCamera>>,
+    ui_setting: &UISetting,
+) -> DetailLevel {
+    match camera.get_single() {
+        Ok((projection, transform)) => {
+            let (start, end) = visible_range(projection, transform);
+            ui_setting.detail_level(end - start)
+        }
+        Err(_) => DetailLevel::Glyphs,
+    }
+}
+
+// Tracks drawn as histograms rather than one entity per feature, hidden ones are neither
+fn draws_density(track: &Track, detail: DetailLevel) -> bool {
+    track.mode == DisplayMode::Density || detail == DetailLevel::Density
+}
+
+// Spawns the features coming into view and despawns the ones that went far enough out of it.
+// Despawning uses twice the margin so small moves back and forth don't churn entities.
+fn stream_features(
+    mut commands: Commands,
+    mut ev_cameramoved: EventReader<CameraMoved>,
+    indices: Option<ResMut<TrackIndices>>,
+    bstate: Res<BrowserState>,
+    ui_setting: Res<UISetting>,
+    camera: Query<(&Projection, &Transform), With<MainCamera>>,
+) {
+    if ev_cameramoved.iter().count() == 0 {
+        return;
+    }
+
+    let mut indices = match indices {
+        Some(x) => x,
+        None => return,
+    };
+
+    let length = bstate.landmark.as_ref().map(|x| x.1).unwrap_or(0);
+
+    let detail = detail_level(&camera, &ui_setting);
+    let (start, end) = camera_bases(&camera, VISIBLE_MARGIN, length);
+    let (keep_start, keep_end) = camera_bases(&camera, VISIBLE_MARGIN * 2.0, length);
+
+    for index in indices.indices.iter_mut() {
+        let track = match bstate.track(index.track) {
+            Some(x) if x.visible && !draws_density(x, detail) => x,
+            // Hidden, or draw_density takes over
+            _ => {
+                for (_, e) in index.spawned.drain() {
+                    commands.entity(e).despawn_recursive();
+                }
+                continue;
+            }
+        };
+
+        let keep = index
+            .overlapping_positions(keep_start, keep_end)
+            .into_iter()
+            .collect::<HashSet<usize>>();
+
+        index.spawned.retain(|n, e| {
+            if keep.contains(n) {
+                return true;
+            }
+            commands.entity(*e).despawn_recursive();
+            false
+        });
+
+        for n in index.overlapping_positions(start, end) {
+            if index.spawned.contains_key(&n) {
+                continue;
+            }
+
+            let mut entity = match index.variants.get(n) {
+                Some(x) => commands.spawn_bundle(entity_bundle_from_variant(x.clone())),
+                None => {
+                    let feature = index.features.get(n).unwrap().clone();
+                    commands.spawn_bundle(entity_bundle_from_gff3_feature(feature))
+                }
+            };
+            let e = entity.insert(InTrack(track.id)).id();
+            index.spawned.insert(n, e);
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct DensityBin;
+
+const DENSITY_BINS: usize = 200;
+const DENSITY_FILL: f32 = 0.8; // Of the lane, for the fullest bin
+
+// Feature counts in bins across the visible range, redrawn on every move while zoomed out
+fn draw_density(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut ev_cameramoved: EventReader<CameraMoved>,
+    indices: Option<Res<TrackIndices>>,
+    bstate: Res<BrowserState>,
+    ui_setting: Res<UISetting>,
+    camera: Query<(&Projection, &Transform), With<MainCamera>>,
+    bins: Query<(Entity, &Handle<Mesh>), With<DensityBin>>,
+    mut glyphs: ResMut<GlyphAssets>,
+) {
+    if ev_cameramoved.iter().count() == 0 {
+        return;
+    }
+
+    for (e, mesh) in bins.iter() {
+        meshes.remove(mesh);
+        commands.entity(e).despawn_recursive();
+    }
+
+    let indices = match indices {
+        Some(x) => x,
+        None => return,
+    };
+
+    let length = bstate.landmark.as_ref().map(|x| x.1).unwrap_or(0);
+    let (start, end) = camera_bases(&camera, 0.0, length);
+    if start >= end {
+        return;
+    }
+
+    let detail = detail_level(&camera, &ui_setting);
+    let bin_size = ((end - start) + DENSITY_BINS - 1) / DENSITY_BINS;
+    let mut bars = Vec::new();
+
+    for index in indices.indices.iter() {
+        let lane = match bstate.track(index.track) {
+            Some(x) if draws_density(x, detail) => bstate.lane(x.id),
+            _ => None,
+        };
+        let (top, bottom) = match lane {
+            Some(x) => x,
+            None => continue,
+        };
+
+        let counts = (start..end)
+            .step_by(bin_size)
+            .map(|x| (x, index.count_overlapping(x, x + bin_size)))
+            .collect::<Vec<(usize, usize)>>();
+
+        let max = counts.iter().map(|x| x.1).max().unwrap_or(0);
+        if max == 0 {
+            continue;
+        }
+
+        // Bars grow up from the bottom of the lane
+        let fill = (top - bottom) * DENSITY_FILL;
+        bars.extend(counts.into_iter().filter(|x| x.1 > 0).map(|(bin_start, count)| {
+            let height = count as f32 / max as f32 * fill;
+            (
+                Vec2::new(
+                    bin_start as f32 + bin_size as f32 / 2.0,
+                    bottom + height / 2.0,
+                ),
+                Vec2::new(bin_size as f32, height),
+            )
+        }));
+    }
+
+    if bars.is_empty() {
+        return;
+    }
+
+    // Every track's histogram is a single mesh, replaced on every redraw
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(batched_rectangles(&bars)),
+            material: glyphs.material(&mut materials, Color::GREEN),
+            ..Default::default()
+        })
+        .insert(DensityBin)
+        .insert(SequenceViewItem);
+}
+
+#[derive(Component)]
+pub struct SignalBar;
+
+const SIGNAL_BINS: usize = 1000;
+const SIGNAL_COLOR: Color = Color::rgb(0.2, 0.3, 0.8);
+
+// bedGraph tracks, the value with the largest magnitude in each bin, scaled to what's in view
+fn draw_signal(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut ev_cameramoved: EventReader<CameraMoved>,
+    bstate: Res<BrowserState>,
+    camera: Query<(&Projection, &Transform), With<MainCamera>>,
+    bars: Query<(Entity, &Handle<Mesh>), With<SignalBar>>,
+    mut glyphs: ResMut<GlyphAssets>,
+) {
+    if ev_cameramoved.iter().count() == 0 {
+        return;
+    }
+
+    for (e, mesh) in bars.iter() {
+        meshes.remove(mesh);
+        commands.entity(e).despawn_recursive();
+    }
+
+    let (landmark, length) = match &bstate.landmark {
+        Some(x) => x,
+        None => return,
+    };
+    let (start, end) = camera_bases(&camera, 0.0, *length);
+    if start >= end {
+        return;
+    }
+
+    let bin_size = ((end - start) + SIGNAL_BINS - 1) / SIGNAL_BINS;
+    let bin_count = (end - start + bin_size - 1) / bin_size;
+
+    for track in bstate.tracks.iter().filter(|x| x.visible) {
+        let bed = match &*track.source {
+            TrackSource::Bed(x) if x.is_graph() => x,
+            _ => continue,
+        };
+        let (top, bottom) = match bstate.lane(track.id) {
+            Some(x) => x,
+            None => continue,
+        };
+
+        let mut bins: Vec<Option<f64>> = vec![None; bin_count];
+        for &(signal_start, signal_end, value) in bed.signal(landmark, start, end) {
+            let first = (signal_start.max(start) - start) / bin_size;
+            let last = (signal_end.min(end).max(signal_start + 1) - 1 - start) / bin_size;
+            for bin in bins[first..=last.min(bin_count - 1)].iter_mut() {
+                if bin.map_or(true, |x| value.abs() > x.abs()) {
+                    *bin = Some(value);
+                }
+            }
+        }
+
+        // Bars grow from zero, at the bottom of the lane unless there are negative values
+        let low = bins.iter().flatten().fold(0.0f64, |a, &b| a.min(b));
+        let high = bins.iter().flatten().fold(0.0f64, |a, &b| a.max(b));
+        if high - low <= 0.0 {
+            continue;
+        }
+
+        let fill = (top - bottom) * DENSITY_FILL;
+        let scale = fill / (high - low) as f32;
+        let zero = bottom + -low as f32 * scale;
+
+        let rectangles = bins
+            .iter()
+            .enumerate()
+            .filter_map(|(n, x)| x.map(|x| (n, x as f32 * scale)))
+            .filter(|x| x.1 != 0.0)
+            .map(|(n, height)| {
+                (
+                    Vec2::new(
+                        (start + n * bin_size) as f32 + bin_size as f32 / 2.0,
+                        zero + height / 2.0,
+                    ),
+                    Vec2::new(bin_size as f32, height.abs()),
+                )
+            })
+            .collect::<Vec<(Vec2, Vec2)>>();
+
+        if rectangles.is_empty() {
+            continue;
+        }
+
+        let color = match bed.track.color {
+            Some([r, g, b]) => Color::rgb_u8(r, g, b),
+            None => SIGNAL_COLOR,
+        };
+
+        // A mesh per track, its color comes from the track line
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(batched_rectangles(&rectangles)),
+                material: glyphs.material(&mut materials, color),
+                ..Default::default()
+            })
+            .insert(SignalBar)
+            .insert(SequenceViewItem);
+    }
+}
+
+#[derive(Component)]
+pub struct BaseGlyph;
+
+// Past this many the letters would overlap anyway
+const MAX_BASES: usize = 1000;
+
+fn base_color(base: u8) -> Color {
+    match base.to_ascii_uppercase() {
+        b'A' => Color::GREEN,
+        b'C' => Color::BLUE,
+        b'G' => Color::ORANGE,
+        b'T' => Color::RED,
+        _ => Color::GRAY,
+    }
+}
+
+// Fully zoomed in, the reference sequence above the landmark, one colored quad and letter a base
+fn draw_bases(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut glyphs: ResMut<GlyphAssets>,
+    mut ev_cameramoved: EventReader<CameraMoved>,
+    asset_server: Res<AssetServer>,
+    bstate: Res<BrowserState>,
+    ui_setting: Res<UISetting>,
+    camera: Query<(&Projection, &Transform), With<MainCamera>>,
+    drawn: Query<Entity, With<BaseGlyph>>,
+    mut failed: Local<Option<String>>, // Landmark the last fetch failed on, reported once
+) {
+    if ev_cameramoved.iter().count() == 0 {
+        return;
+    }
+
+    for e in drawn.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+
+    if detail_level(&camera, &ui_setting) != DetailLevel::Bases {
+        return;
+    }
+
+    let (fasta, (landmark, length)) = match (&bstate.fasta, &bstate.landmark) {
+        (Some(fasta), Some(landmark)) => (fasta, landmark),
+        _ => return,
+    };
+
+    let (start, end) = camera_bases(&camera, VISIBLE_MARGIN, *length);
+    if end - start > MAX_BASES {
+        return;
+    }
+
+    let bases = match fasta.fetch(landmark, start, end) {
+        Ok(x) => x,
+        Err(err) => {
+            if failed.as_deref() != Some(landmark.as_str()) {
+                println!("Unable to fetch {}:{}-{}: {}", landmark, start, end, err);
+                *failed = Some(landmark.clone());
+            }
+            return;
+        }
+    };
+    *failed = None;
+
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 14.,
+        color: Color::WHITE,
+    };
+    for (n, base) in bases.into_iter().enumerate() {
+        let id = commands
+            .spawn_bundle(PbrBundle {
+                mesh: glyphs.quad.clone(),
+                material: glyphs.material(&mut materials, base_color(base)),
+                transform: rectangle(
+                    Vec3::new((start + n) as f32 + 0.5, 0.6, 0.0),
+                    Vec2::new(0.9, 0.4),
+                ),
+                ..Default::default()
+            })
+            .insert(LabelBase)
+            .insert(BaseGlyph)
+            .insert(SequenceViewItem)
+            .id();
+
+        commands
+            .spawn_bundle(TextBundle {
+                text: Text::from_section((base as char).to_string(), text_style.clone()),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Label::belongs_to(id))
+            .insert(BaseGlyph)
+            .insert(SequenceViewItem);
+    }
+}
+
+// Segments far enough out of view are despawned, their neighbours are checked again so they come
+// back once scrolled to
+fn stream_segments(
+    mut commands: Commands,
+    mut ev_cameramoved: EventReader<CameraMoved>,
+    mut registry: ResMut<EntityRegistry>,
+    bstate: Res<BrowserState>,
+    camera: Query<(&Projection, &Transform), With<MainCamera>>,
+    segments: Query<(Entity, &ID, &Transform, &Collider), With<HasLinks>>,
+    lines: Query<(Entity, &LinkLine)>,
+) {
+    if ev_cameramoved.iter().count() == 0 {
+        return;
+    }
+
+    let gfa = match &bstate.gfa {
+        Some(x) => x,
+        None => return,
+    };
+
+    let (keep_start, keep_end) = match camera.get_single() {
+        Ok((projection, transform)) => visible_range(projection, transform),
+        Err(_) => return,
+    };
+    let margin = (keep_end - keep_start) * VISIBLE_MARGIN * 2.0;
+    let (keep_start, keep_end) = (keep_start - margin, keep_end + margin);
+
+    let mut despawned = HashSet::new();
+    for (e, id, transform, collider) in segments.iter() {
+        let start = transform.translation.x - collider.size.x / 2.0;
+        let end = transform.translation.x + collider.size.x / 2.0;
+        if end >= keep_start && start <= keep_end {
+            continue;
+        }
+
+        commands.entity(e).despawn_recursive();
+        registry.registry.remove(&id.id);
+        despawned.insert(e);
 
-    println!("Drawing {}", features.len());
+        for link in gfa.links_atlas.get(&id.id).into_iter().flatten() {
+            let neighbour = if link.from == id.id { &link.to } else { &link.from };
+            if let Some(x) = registry.registry.get(neighbour) {
+                commands.entity(*x).insert(CheckLinks);
+            }
+        }
+    }
 
-    commands.spawn_batch(features.into_iter().map(entity_bundle_from_gff3_feature));
+    if despawned.is_empty() {
+        return;
+    }
+
+    for (e, line) in lines.iter() {
+        if despawned.contains(&line.from) || despawned.contains(&line.to) {
+            commands.entity(e).despawn_recursive();
+            // Drawn again if the other end comes back
+            if !despawned.contains(&line.from) {
+                commands.entity(line.from).remove::<DrawnLinks>();
+            }
+        }
+    }
 }
 
 fn entity_bundle_from_gff3_feature(feature: Feature) -> (SequenceViewItem, Name, Feature) {
-    (SequenceViewItem, Name::from("Gene"), feature)
+    (SequenceViewItem, Name::from(feature.name.clone()), feature)
+}
+
+fn entity_bundle_from_variant(variant: Variant) -> (SequenceViewItem, Name, Variant) {
+    let name = variant.id.clone().unwrap_or_default();
+    (SequenceViewItem, Name::from(name), variant)
 }
 
 #[derive(Component)]
 pub struct SequenceViewItemDrawn;
 
+fn feature_color(feature_type: &str) -> Color {
+    match feature_type.to_ascii_lowercase().as_str() {
+        "mrna" | "transcript" | "gene" | "cds" => Color::GREEN,
+        "ncrna" | "lnc_rna" | "lncrna" | "mirna" | "snrna" | "snorna" => Color::PURPLE,
+        "trna" | "rrna" => Color::ORANGE,
+        x if x.starts_with("pseudogen") => Color::GRAY,
+        x if x.contains("repeat") || x.contains("transposon") => Color::MAROON,
+        _ => Color::SEA_GREEN,
+    }
+}
+
+// Of the glyph height: UTRs and non-coding exons are thinner than CDS, introns thinner still
+const UTR_HEIGHT: f32 = 0.5;
+const EXON_HEIGHT: f32 = 0.75;
+const INTRON_HEIGHT: f32 = 0.1;
+const CHEVRON_HEIGHT: f32 = 0.6;
+
+// Chevrons are at most this much of the visible span wide, and of their intron
+const CHEVRON_SPAN: f32 = 0.01;
+const CHEVRON_INTRON: f32 = 0.5;
+
+// Resized by rescale_chevrons as the view zooms, the rest of a feature is in bp and scales with it
+#[derive(Component)]
+pub struct Chevron {
+    intron: f32, // Length, bp
+}
+
+impl Chevron {
+    fn width(&self, span: f32) -> f32 {
+        (self.intron * CHEVRON_INTRON).min(span * CHEVRON_SPAN)
+    }
+}
+
+fn rescale_chevrons(
+    mut ev_cameramoved: EventReader<CameraMoved>,
+    camera: Query<(&Projection, &Transform), (With<MainCamera>, Without<Chevron>)>,
+    mut chevrons: Query<(&Chevron, &mut Transform)>,
+) {
+    if ev_cameramoved.iter().count() == 0 {
+        return;
+    }
+
+    let span = match camera.get_single() {
+        Ok((projection, transform)) => {
+            let (start, end) = visible_range(projection, transform);
+            end - start
+        }
+        Err(_) => return,
+    };
+
+    for (chevron, mut transform) in chevrons.iter_mut() {
+        transform.scale.x = chevron.width(span);
+    }
+}
+
+// Transcript models: blocks for exons, lines for introns with chevrons pointing along the strand.
+// What isn't picked on its own is one mesh per feature: the introns, and the blocks of squished
+// rows too thin to click.
 fn draw_feature(
     mut commands: Commands,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
-    query: Query<(Entity, &Feature), (With<SequenceViewItem>, Without<SequenceViewItemDrawn>)>,
+    mut glyphs: ResMut<GlyphAssets>,
+    bstate: Res<BrowserState>,
+    camera: Query<(&Projection, &Transform), With<MainCamera>>,
+    query: Query<
+        (Entity, &Feature, &InTrack),
+        (With<SequenceViewItem>, Without<SequenceViewItemDrawn>),
+    >,
 ) {
+    let span = match camera.get_single() {
+        Ok((projection, transform)) => {
+            let (start, end) = visible_range(projection, transform);
+            end - start
+        }
+        Err(_) => return,
+    };
+
     // TODO: Parallel in 0.6
-    for (e, feature) in query.iter() {
-        let mut entity = commands.entity(e);
+    for (e, feature, track) in query.iter() {
+        let mode = bstate.track(track.0).map(|x| x.mode);
+        let row_height = mode.and_then(|x| x.row_height());
+
+        // Packed in rows from the top of its track's lane, or centered in it
+        let (y, height) = match (bstate.lane(track.0), row_height) {
+            (Some((top, _)), Some(row)) => (top - (feature.y_offset as f32 + 0.5) * row, row / 2.0),
+            (Some((top, bottom)), None) => ((top + bottom) / 2.0, GLYPH_HEIGHT),
+            (None, _) => continue,
+        };
 
-        let width = (feature.end - feature.start) as f32;
-        let width = width.abs();
+        let model = TranscriptModel::new(feature);
+        let color = match feature.color {
+            Some([r, g, b]) => Color::rgb_u8(r, g, b),
+            None => feature_color(&feature.feature_type),
+        };
+        let material = glyphs.material(&mut materials, color);
+        let width = (model.end - model.start) as f32;
 
-        let coords = Vec3::new(
-            ((feature.start as f32 + feature.end as f32) / 2.0) - 1.,
-            -2.,
-            0.0,
-        );
+        let rotation = match model.strand {
+            Some(Orientation::Negative) => Quat::from_rotation_z(std::f32::consts::PI),
+            _ => Quat::IDENTITY,
+        };
 
-        entity
-            .insert_bundle(PbrBundle {
-                mesh: meshes.add(Mesh::from(shape::Quad {
-                    size: Vec2::new(0.20, width), //(feature.end - feature.start) as f32),
-                    flip: false,
-                })),
-                material: materials.add(StandardMaterial {
-                    base_color: Color::GREEN,
-                    ..Default::default()
-                }),
-                transform: Transform {
-                    rotation: Quat::from_euler(EulerRot::XYZ, 0., 0., std::f32::consts::FRAC_PI_2), // 1.5708),
-                    translation: coords,
-                    scale: Vec3::new(1., 1., 1.),
-                },
+        let mut children = Vec::new();
+        let mut batched = Vec::new();
+
+        for &(start, end) in model.introns.iter() {
+            let center = (start + end) as f32 / 2.0;
+            let length = (end - start) as f32;
+
+            batched.push((
+                Vec2::new(center, y),
+                Vec2::new(length, height * INTRON_HEIGHT),
+            ));
+
+            // Unstranded features get none
+            if model.strand.is_none() {
+                continue;
+            }
+
+            let chevron = Chevron { intron: length };
+            children.push(
+                commands
+                    .spawn_bundle(PbrBundle {
+                        mesh: glyphs.chevron.clone(),
+                        material: material.clone(),
+                        transform: Transform::from_translation(Vec3::new(center, y, 0.01))
+                            .with_rotation(rotation)
+                            .with_scale(Vec3::new(
+                                chevron.width(span),
+                                height * CHEVRON_HEIGHT,
+                                1.0,
+                            )),
+                        ..Default::default()
+                    })
+                    .insert(chevron)
+                    .id(),
+            );
+        }
+
+        for &(start, end, kind) in model.blocks.iter() {
+            let block_height = match kind {
+                BlockKind::Cds => height,
+                BlockKind::Utr => height * UTR_HEIGHT,
+                BlockKind::Exon => height * EXON_HEIGHT,
+            };
+
+            if mode == Some(DisplayMode::Squished) {
+                batched.push((
+                    Vec2::new((start + end) as f32 / 2.0, y),
+                    Vec2::new((end - start) as f32, block_height),
+                ));
+                continue;
+            }
+
+            children.push(
+                commands
+                    .spawn_bundle(PbrBundle {
+                        mesh: glyphs.quad.clone(),
+                        material: material.clone(),
+                        transform: rectangle(
+                            Vec3::new((start + end) as f32 / 2.0, y, 0.02),
+                            Vec2::new((end - start) as f32, block_height),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert_bundle(PickableBundle::default())
+                    .id(),
+            );
+        }
+
+        if !batched.is_empty() {
+            children.push(
+                commands
+                    .spawn_bundle(PbrBundle {
+                        mesh: meshes.add(batched_rectangles(&batched)),
+                        material: material.clone(),
+                        ..Default::default()
+                    })
+                    .id(),
+            );
+        }
+
+        // The parts are children so they are despawned with the feature
+        commands
+            .entity(e)
+            .insert_bundle(SpatialBundle::default())
+            .insert(SequenceViewItemDrawn)
+            .insert(Collider {
+                size: Vec2::new(width, height),
+            })
+            .push_children(&children);
+    }
+}
+
+fn variant_color(kind: VariantKind) -> Color {
+    match kind {
+        VariantKind::Snv => Color::rgb(0.2, 0.5, 0.9),
+        VariantKind::Mnv => Color::CYAN,
+        VariantKind::Insertion => Color::PURPLE,
+        VariantKind::Deletion => Color::RED,
+        VariantKind::Complex => Color::ORANGE,
+        VariantKind::Structural => Color::YELLOW,
+        VariantKind::Reference => Color::GRAY,
+    }
+}
+
+fn genotype_color(call: GenotypeCall) -> Color {
+    match call {
+        GenotypeCall::HomRef => Color::rgb(0.6, 0.6, 0.6),
+        GenotypeCall::Het => Color::rgb(0.2, 0.5, 0.9),
+        GenotypeCall::HomAlt => Color::CYAN,
+        GenotypeCall::NoCall => Color::rgb(0.25, 0.25, 0.25),
+    }
+}
+
+// Single base sites are widened to this much of the visible span, so they show zoomed out
+const TICK_SPAN: f32 = 0.002;
+
+// Of the row, the rest is the gap between cells
+const CELL_HEIGHT: f32 = 0.8;
+
+// Ticks and genotype cells, widened by rescale_variants as the view zooms
+#[derive(Component)]
+pub struct VariantGlyph {
+    start: f32,  // 0-based, bp
+    length: f32, // bp
+}
+
+impl VariantGlyph {
+    fn width(&self, span: f32) -> f32 {
+        self.length.max(span * TICK_SPAN)
+    }
+
+    fn resize(&self, transform: &mut Transform, span: f32) {
+        let width = self.width(span);
+        transform.translation.x = self.start + width / 2.0;
+        transform.scale.x = width;
+    }
+}
+
+fn rescale_variants(
+    mut ev_cameramoved: EventReader<CameraMoved>,
+    camera: Query<(&Projection, &Transform), (With<MainCamera>, Without<VariantGlyph>)>,
+    mut glyphs: Query<(&VariantGlyph, &mut Transform)>,
+) {
+    if ev_cameramoved.iter().count() == 0 {
+        return;
+    }
+
+    let span = match camera.get_single() {
+        Ok((projection, transform)) => {
+            let (start, end) = visible_range(projection, transform);
+            end - start
+        }
+        Err(_) => return,
+    };
+
+    for (glyph, mut transform) in glyphs.iter_mut() {
+        glyph.resize(&mut transform, span);
+    }
+}
+
+// A tick for the site, colored by type, and in packed modes a grid cell per sample under it
+fn draw_variant(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut glyphs: ResMut<GlyphAssets>,
+    bstate: Res<BrowserState>,
+    camera: Query<(&Projection, &Transform), With<MainCamera>>,
+    query: Query<
+        (Entity, &Variant, &InTrack),
+        (With<SequenceViewItem>, Without<SequenceViewItemDrawn>),
+    >,
+) {
+    let span = match camera.get_single() {
+        Ok((projection, transform)) => {
+            let (start, end) = visible_range(projection, transform);
+            end - start
+        }
+        Err(_) => return,
+    };
+
+    for (e, variant, track) in query.iter() {
+        let row_height = bstate.track(track.0).and_then(|x| x.mode.row_height());
+
+        // Sites in the first row and samples in the ones under it, or centered in the lane
+        let (top, y, height) = match (bstate.lane(track.0), row_height) {
+            (Some((top, _)), Some(row)) => (top, top - row / 2.0, row * CELL_HEIGHT),
+            (Some((top, bottom)), None) => (top, (top + bottom) / 2.0, GLYPH_HEIGHT),
+            (None, _) => continue,
+        };
+
+        let start = variant.position.saturating_sub(1) as f32;
+        let glyph = || VariantGlyph {
+            start,
+            length: variant.end() as f32 - start,
+        };
+        let width = glyph().width(span);
+        let x = start + width / 2.0;
+
+        let mut children = vec![commands
+            .spawn_bundle(PbrBundle {
+                mesh: glyphs.quad.clone(),
+                material: glyphs.material(&mut materials, variant_color(variant.kind())),
+                transform: rectangle(Vec3::new(x, y, 0.02), Vec2::new(width, height)),
                 ..Default::default()
             })
-            .insert(SequenceViewItemDrawn)
             .insert_bundle(PickableBundle::default())
+            .insert(glyph())
+            .id()];
+
+        if let Some(row) = row_height {
+            for n in 0..variant.samples.len() {
+                let call = variant
+                    .genotype(n)
+                    .map_or(GenotypeCall::NoCall, |x| x.call());
+                let y = top - (n as f32 + 1.5) * row;
+
+                children.push(
+                    commands
+                        .spawn_bundle(PbrBundle {
+                            mesh: glyphs.quad.clone(),
+                            material: glyphs.material(&mut materials, genotype_color(call)),
+                            transform: rectangle(Vec3::new(x, y, 0.02), Vec2::new(width, height)),
+                            ..Default::default()
+                        })
+                        .insert_bundle(PickableBundle::default())
+                        .insert(glyph())
+                        .id(),
+                );
+            }
+        }
+
+        // Clicking any of the children selects the variant, see VariantInspectorPlugin
+        commands
+            .entity(e)
+            .insert_bundle(SpatialBundle::default())
+            .insert(SequenceViewItemDrawn)
             .insert(Collider {
-                size: Vec2::new(width, 0.2), //(feature.end - feature.start) as f32),
-            });
+                size: Vec2::new(width, height),
+            })
+            .push_children(&children);
     }
 }
 
@@ -288,7 +1230,7 @@ fn draw_lines(
     registry: Res<EntityRegistry>,
     bstate: Res<BrowserState>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    mut glyphs: ResMut<GlyphAssets>,
 ) {
     for (e, t, c, id) in query.iter() {
         let links = bstate
@@ -316,30 +1258,27 @@ fn draw_lines(
             if let Ok((te, tt, tc, tid)) = query.get(*linkto.unwrap()) {
                 let mut target_position = tt.translation;
                 target_position.x -= tc.size.x / 2.0; // Left align
+                target_position.x += link_overlap(link) as f32;
 
+                // Spans from the end of from to the start of to, thicker with more support
+                let width = (target_position.x - position.x).abs().max(1.0);
 
                 let id = commands
                     .spawn_bundle(PbrBundle {
-                        mesh: meshes.add(Mesh::from(shape::Quad {
-                            size: Vec2::new(50.0, 0.4),
-                            flip: false,
-                        })),
-                        material: materials.add(StandardMaterial {
-                            base_color: Color::YELLOW,
-                            emissive: Color::WHITE * 10.0f32,
-                            ..Default::default()
-                        }),
-                        transform: Transform {
-                            rotation: Quat::from_euler(EulerRot::XYZ, 0., 0., 0.), // std::f32::consts::FRAC_PI_2), // 1.5708),
-                            translation: Vec3::new(
-                                target_position.x,
+                        mesh: glyphs.quad.clone(),
+                        material: glyphs.emissive_material(&mut materials, Color::YELLOW),
+                        transform: rectangle(
+                            Vec3::new(
+                                (position.x + target_position.x) / 2.0,
                                 target_position.y,
                                 0.,
                             ),
-                            scale: Vec3::new(1., 1., 1.),
-                        },
+                            Vec2::new(width, link_thickness(link)),
+                        ),
                         ..default()
-                    });
+                    })
+                    .insert(SequenceViewItem)
+                    .insert(LinkLine { from: e, to: te });
                 commands.entity(e).insert(DrawnLinks);
             }
         }
@@ -386,14 +1325,30 @@ fn draw_lines(
     }
 } */
 
+// Bases shared by the end of from and the start of to
+fn link_overlap(link: &Link) -> usize {
+    link.overlap
+        .as_ref()
+        .map(|x| x.reference_length())
+        .unwrap_or(0)
+}
+
+fn link_thickness(link: &Link) -> f32 {
+    match link.coverage() {
+        Some(x) => (0.1 + (x as f32).ln_1p() * 0.1).min(1.0),
+        None => 0.4,
+    }
+}
+
 fn check_links(
     mut commands: Commands,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    mut glyphs: ResMut<GlyphAssets>,
     mut registry: ResMut<EntityRegistry>,
     mut expansion_rounds: ResMut<ExpansionRounds>,
     bstate: Res<BrowserState>,
     query: Query<(Entity, &ID, &Transform, &Collider), With<CheckLinks>>,
+    camera: Query<(&Projection, &Transform), With<MainCamera>>,
 ) {
     if bstate.gfa.is_none() {
         println!("No check_links: bstate gfa is none");
@@ -406,7 +1361,22 @@ fn check_links(
 
     expansion_rounds.round += 1;
 
+    // Only expand what is in view, the rest keeps CheckLinks until it is scrolled to
+    let visible = camera.get_single().ok().map(|(projection, transform)| {
+        let (start, end) = visible_range(projection, transform);
+        let margin = (end - start) * VISIBLE_MARGIN;
+        (start - margin, end + margin)
+    });
+
     for (e, id, transform, collider) in query.iter() {
+        if let Some((start, end)) = visible {
+            if transform.translation.x + collider.size.x / 2.0 < start
+                || transform.translation.x - collider.size.x / 2.0 > end
+            {
+                continue;
+            }
+        }
+
         let mut entity = commands.entity(e);
 
         // No more checking for this one...
@@ -421,7 +1391,8 @@ fn check_links(
         entity.insert(HasLinks);
 
         for link in links.unwrap() {
-            commands.spawn().insert(link.as_ref().clone());
+            // Neighbours are pulled in by the overlap so shared bases are drawn once
+            let overlap = link_overlap(link) as f32;
 
             let from_entity = registry.registry.get(&link.from);
 
@@ -444,26 +1415,17 @@ fn check_links(
 
                 let id = commands
                     .spawn_bundle(PbrBundle {
-                        mesh: meshes.add(Mesh::from(shape::Quad {
-                            size: Vec2::new(length as f32, 0.4),
-                            flip: false,
-                        })),
-                        material: materials.add(StandardMaterial {
-                            base_color: Color::YELLOW,
-                            // emissive: Color::WHITE * 10.0f32
-                            ..Default::default()
-                        }),
-                        transform: Transform {
-                            rotation: Quat::from_euler(EulerRot::XYZ, 0., 0., 0.), // std::f32::consts::FRAC_PI_2), // 1.5708),
-                            translation: Vec3::new(
-                                transform.translation.x
-                                    - collider.size.x // / 2.0
-                                    - length as f32, // / 2.0
+                        mesh: glyphs.quad.clone(),
+                        material: glyphs.material(&mut materials, Color::YELLOW),
+                        transform: rectangle(
+                            Vec3::new(
+                                transform.translation.x - (collider.size.x + length as f32) / 2.0
+                                    + overlap,
                                 0.,
                                 0.,
                             ),
-                            scale: Vec3::new(1., 1., 1.),
-                        },
+                            Vec2::new(length as f32, 0.4),
+                        ),
 
                         //        material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
                         //        material: materials.add(StandardMaterial {
@@ -507,24 +1469,17 @@ fn check_links(
 
                 let id = commands
                     .spawn_bundle(PbrBundle {
-                        mesh: meshes.add(Mesh::from(shape::Quad {
-                            size: Vec2::new(length as f32, 0.4),
-                            flip: false,
-                        })),
-                        material: materials.add(StandardMaterial {
-                            base_color: Color::YELLOW,
-                            // emissive: Color::WHITE * 10.0f32,
-                            ..Default::default()
-                        }),
-                        transform: Transform {
-                            rotation: Quat::from_euler(EulerRot::XYZ, 0., 0., 0.), // std::f32::consts::FRAC_PI_2), // 1.5708),
-                            translation: Vec3::new(
-                                transform.translation.x + collider.size.x + length as f32,
+                        mesh: glyphs.quad.clone(),
+                        material: glyphs.material(&mut materials, Color::YELLOW),
+                        transform: rectangle(
+                            Vec3::new(
+                                transform.translation.x + (collider.size.x + length as f32) / 2.0
+                                    - overlap,
                                 0.,
                                 0.,
                             ),
-                            scale: Vec3::new(1., 1., 1.),
-                        },
+                            Vec2::new(length as f32, 0.4),
+                        ),
 
                         //        material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
                         //        material: materials.add(StandardMaterial {
@@ -645,6 +1600,8 @@ fn cleanup(mut commands: Commands, q: Query<Entity, With<SequenceViewItem>>) {
     for e in q.iter() {
         commands.entity(e).despawn_recursive();
     }
+
+    commands.remove_resource::<TrackIndices>();
 }
 
 /*
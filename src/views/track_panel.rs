@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use crate::structs::*;
+use crate::views::menu_bar::OpenPath;
+
+pub struct TrackPanelPlugin;
+impl Plugin for TrackPanelPlugin {
+    fn build(&self, app: &mut App) {
+        // After the menu bar so the side panel starts under it
+        app.add_system(track_panel.after("menu_bar"));
+    }
+}
+
+// Lane heights, in world units
+const MIN_HEIGHT: f32 = 0.5;
+const MAX_HEIGHT: f32 = 10.0;
+
+fn track_panel(
+    mut egui_ctx: ResMut<EguiContext>,
+    mut bstate: ResMut<BrowserState>,
+    mut open_path: ResMut<OpenPath>,
+    mut ev_tracks: EventWriter<TracksChanged>,
+) {
+    egui::SidePanel::left("tracks")
+        .resizable(true)
+        .default_width(240.0)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.heading("Tracks");
+            if ui.button("Add...").clicked() {
+                open_path.show();
+            }
+            ui.separator();
+
+            let mut changed = false;
+            let mut moved = None;
+            let mut removed = None;
+            let count = bstate.tracks.len();
+
+            for (n, track) in bstate.tracks.iter_mut().enumerate() {
+                ui.push_id(track.id, |ui| {
+                    ui.horizontal(|ui| {
+                        changed |= ui
+                            .checkbox(&mut track.visible, "")
+                            .on_hover_text("Show")
+                            .changed();
+                        // Only shown here, renaming doesn't move anything
+                        ui.add(egui::TextEdit::singleline(&mut track.name).desired_width(160.0))
+                            .on_hover_text(track.source.filename());
+                    });
+
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("mode")
+                            .selected_text(track.mode.name())
+                            .width(90.0)
+                            .show_ui(ui, |ui| {
+                                for mode in DisplayMode::ALL {
+                                    changed |= ui
+                                        .selectable_value(&mut track.mode, mode, mode.name())
+                                        .changed();
+                                }
+                            });
+
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut track.height)
+                                    .clamp_range(MIN_HEIGHT..=MAX_HEIGHT)
+                                    .speed(0.1),
+                            )
+                            .on_hover_text("Height")
+                            .changed();
+
+                        if ui.add_enabled(n > 0, egui::Button::new("⬆")).clicked() {
+                            moved = Some((n, n - 1));
+                        }
+                        if ui
+                            .add_enabled(n + 1 < count, egui::Button::new("⬇"))
+                            .clicked()
+                        {
+                            moved = Some((n, n + 1));
+                        }
+                        if ui.button("🗑").on_hover_text("Remove").clicked() {
+                            removed = Some(n);
+                        }
+                    });
+                });
+                ui.separator();
+            }
+
+            if let Some((a, b)) = moved {
+                bstate.tracks.swap(a, b);
+                changed = true;
+            }
+
+            // The sequence view drops its features once the track is gone
+            if let Some(n) = removed {
+                bstate.tracks.remove(n);
+                changed = true;
+            }
+
+            if changed {
+                ev_tracks.send(TracksChanged);
+            }
+        });
+}
@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use bevy_mod_picking::*;
+
+use crate::core::states::*;
+use crate::parsers::*;
+use crate::structs::*;
+
+pub struct VariantInspectorPlugin;
+impl Plugin for VariantInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectedVariant>()
+            .add_system_set(
+                SystemSet::on_update(AppState::SequenceView)
+                    .with_system(select_variant)
+                    .with_system(variant_inspector),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::SequenceView).with_system(clear_selection),
+            );
+    }
+}
+
+// The last variant clicked in the sequence view, and the track it's from
+#[derive(Default)]
+pub struct SelectedVariant(Option<(usize, Variant)>);
+
+// Ticks and genotype cells are children of the entity holding the variant
+fn select_variant(
+    mut events: EventReader<PickingEvent>,
+    mut selected: ResMut<SelectedVariant>,
+    parents: Query<&Parent>,
+    variants: Query<(&Variant, &InTrack)>,
+) {
+    for event in events.iter() {
+        if let PickingEvent::Clicked(e) = *event {
+            let found = parents.get(e).ok().and_then(|x| variants.get(x.get()).ok());
+            if let Some((variant, track)) = found {
+                selected.0 = Some((track.0, variant.clone()));
+            }
+        }
+    }
+}
+
+fn clear_selection(mut selected: ResMut<SelectedVariant>) {
+    selected.0 = None;
+}
+
+// Header descriptions show on hover
+fn described(ui: &mut egui::Ui, text: &str, field: Option<&HeaderField>) {
+    let response = ui.label(text);
+    if let Some(description) = field.and_then(|x| x.description.as_deref()) {
+        response.on_hover_text(description);
+    }
+}
+
+fn variant_inspector(
+    mut egui_ctx: ResMut<EguiContext>,
+    mut selected: ResMut<SelectedVariant>,
+    bstate: Res<BrowserState>,
+) {
+    let (track, variant) = match selected.0.clone() {
+        Some(x) => x,
+        None => return,
+    };
+
+    // The track can be removed while its variant is shown
+    let header = match bstate.track(track).map(|x| &*x.source) {
+        Some(TrackSource::Vcf(x)) => &x.header,
+        _ => {
+            selected.0 = None;
+            return;
+        }
+    };
+
+    let mut open = true;
+    egui::Window::new("Variant")
+        .open(&mut open)
+        .default_width(360.0)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.heading(format!(
+                "{}:{} {}",
+                variant.landmark,
+                variant.position,
+                variant.id.as_deref().unwrap_or("")
+            ));
+            ui.label(format!(
+                "{} > {} ({})",
+                variant.reference,
+                variant.alternates.join(","),
+                variant.kind().name()
+            ));
+            ui.label(format!(
+                "Quality {}, filter {}",
+                variant
+                    .quality
+                    .map_or("unknown".to_string(), |x| x.to_string()),
+                variant.filter.as_deref().unwrap_or("not applied")
+            ));
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.collapsing("INFO", |ui| {
+                    egui::Grid::new("info").striped(true).show(ui, |ui| {
+                        for (key, value) in variant.info.iter() {
+                            described(ui, key, header.info_field(key));
+                            ui.label(value.as_deref().unwrap_or("yes"));
+                            ui.end_row();
+                        }
+                    });
+                });
+
+                if variant.samples.is_empty() {
+                    return;
+                }
+
+                ui.collapsing("FORMAT", |ui| {
+                    egui::Grid::new("format").striped(true).show(ui, |ui| {
+                        ui.label("");
+                        for key in variant.format.iter() {
+                            described(ui, key, header.format_field(key));
+                        }
+                        ui.end_row();
+
+                        for (n, sample) in header.samples.iter().enumerate() {
+                            ui.label(sample);
+                            for key in variant.format.iter() {
+                                ui.label(variant.sample_field(n, key).unwrap_or("."));
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        });
+
+    if !open {
+        selected.0 = None;
+    }
+}